@@ -120,11 +120,11 @@ def main():
     
     # 2. Benchmark Indexing
     print("Benchmarking Indexing...")
-    db = photon_db.PyHNSW(len(corpus_embeddings), args.dim, args.m, args.ef_construction)
+    db = photon_db.PyHNSW(len(corpus_embeddings), args.dim, args.m, args.ef_construction, args.ef_search)
     
     start_index = time.time()
     for vec in tqdm(corpus_embeddings, desc="Indexing"):
-        db.insert(vec.tolist(), args.m, args.m * 2, args.ef_construction, 1.0)
+        db.insert(vec.tolist())
     end_index = time.time()
     
     index_time_total = end_index - start_index
@@ -145,7 +145,7 @@ def main():
     
     for q_vec in query_embeddings:
         t_start = time.time()
-        res = db.search(q_vec.tolist(), args.k, args.ef_search)
+        res = db.search(q_vec.tolist(), args.k)
         t_end = time.time()
         latencies.append((t_end - t_start) * 1000)
         hnsw_results.append(res)
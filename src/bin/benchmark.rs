@@ -1,6 +1,6 @@
 // use colored::*;
 use colored_text::Colorize;
-use photon_db::HNSW;
+use photon_db::HnswConfig;
 use rayon::prelude::*;
 use std::time::{Duration, Instant};
 use rand::prelude::*;
@@ -58,10 +58,16 @@ fn main() {
         .collect();
 
     // Insert vectors
-    let mut graph = HNSW::new(N_VECTORS, DIM);
+    let mut graph = HnswConfig::new(DIM)
+        .max_elements(N_VECTORS)
+        .m(16)
+        .m_max(32)
+        .ef_construction(100)
+        .m_l(0.5)
+        .build()
+        .expect("Invalid HNSW config");
     for vec in vectors {
-        let id = graph.vectors.insert(&vec);
-        graph.insert(id, 16, 32, 100, 0.5); 
+        graph.insert(&vec);
     }
     
     println!("    {}", "Starting Benchmark...".blue().bold());
@@ -82,14 +88,12 @@ fn main() {
             
             // HNSW Search
             let start = Instant::now();
-            let hnsw_results = graph.search(&query, 1, 64);
+            let hnsw_results = graph.search(&query, 1, Some(64));
             let duration_hnsw = start.elapsed();
             
             let mut match_count = 0;
-            if !bf_results.is_empty() && !hnsw_results.is_empty() {
-                 if bf_results[0].1 == hnsw_results[0].1 {
-                     match_count = 1;
-                 }
+            if !bf_results.is_empty() && !hnsw_results.is_empty() && bf_results[0].1 == hnsw_results[0].1 {
+                match_count = 1;
             }
             (duration_bf, duration_hnsw, match_count)
         })
@@ -101,4 +105,16 @@ fn main() {
     println!("    {}: {}", "Brute Force Avg".blue().bold(), format!("{:?}", total_duration_bf / N_QUERIES as u32).green().bold());
     println!("    {}: {}", "HNSW Search Avg".blue().bold(), format!("{:?}", total_duration_hnsw / N_QUERIES as u32).green().bold());
     println!("    {}: {}", "Recall".blue().bold(), format!("{:.2}%", (correct_matches as f64 / N_QUERIES as f64) * 100.0).green().bold());
+
+    // Same queries through the batch API (wall clock for the whole batch)
+    let queries: Vec<f32> = (0..N_QUERIES)
+        .flat_map(|i| {
+            let mut rng = StdRng::seed_from_u64((N_VECTORS + i) as u64);
+            (0..DIM).map(move |_| rng.random::<f32>())
+        })
+        .collect();
+    let start = Instant::now();
+    let batch_results = graph.search_batch(&queries, 1, Some(64));
+    let duration_batch = start.elapsed();
+    println!("    {}: {} ({} queries)", "HNSW Batch Total".blue().bold(), format!("{:?}", duration_batch).green().bold(), batch_results.len());
 }
@@ -0,0 +1,156 @@
+// Named vectors: one point id, several embedding spaces ("title", "body", ...), each with its
+// own dim / metric / graph. Every space is a full HNSW using the collection's ids as external
+// ids, payloads and deletes live at the collection level so the spaces can't drift apart.
+// A point doesn't have to have a vector in every space.
+
+use crate::{HnswConfig, Payload, HNSW};
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq, Default)]
+pub struct Collection {
+    pub spaces: BTreeMap<String, HNSW>,
+    pub payloads: BTreeMap<usize, Payload>,
+    // live point ids
+    pub points: BTreeSet<usize>,
+    pub next_id: usize,
+}
+
+impl Collection {
+    pub fn new() -> Self {
+        Collection::default()
+    }
+
+    pub fn add_space(&mut self, name: &str, config: HnswConfig) -> Result<(), String> {
+        if self.spaces.contains_key(name) {
+            return Err(format!("Space already exists: {}", name));
+        }
+        self.spaces.insert(name.to_string(), config.build()?);
+        Ok(())
+    }
+
+    pub fn space(&self, name: &str) -> Result<&HNSW, String> {
+        self.spaces.get(name).ok_or_else(|| format!("Unknown space: {}", name))
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.points.contains(&id)
+    }
+
+    fn check(&self, vectors: &[(&str, &[f32])]) -> Result<(), String> {
+        if vectors.is_empty() {
+            return Err("A point needs at least one named vector".to_string());
+        }
+        for (name, vec) in vectors {
+            let space = self.space(name)?;
+            if vec.len() != space.vectors.dim {
+                return Err(format!(
+                    "Vector dimension mismatch in {}: expected {}, got {}",
+                    name,
+                    space.vectors.dim,
+                    vec.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Nothing is inserted unless every (space, vector) pair is valid
+    pub fn insert(&mut self, vectors: &[(&str, &[f32])], payload: Option<Payload>) -> Result<usize, String> {
+        self.check(vectors)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        for (name, vec) in vectors {
+            self.set_in_space(name, id, vec);
+        }
+        if let Some(payload) = payload {
+            self.payloads.insert(id, payload);
+        }
+        self.points.insert(id);
+        Ok(id)
+    }
+
+    fn set_in_space(&mut self, name: &str, id: usize, vec: &[f32]) {
+        let space = self.spaces.get_mut(name).unwrap();
+        if !space.update(id, vec) {
+            space.insert_with_id(id, vec);
+            space.next_id = space.next_id.max(id + 1);
+        }
+    }
+
+    // Adds or replaces the vector of an existing point in one space
+    pub fn set_vector(&mut self, id: usize, name: &str, vec: &[f32]) -> Result<(), String> {
+        if !self.contains(id) {
+            return Err(format!("Unknown id: {}", id));
+        }
+        self.check(&[(name, vec)])?;
+        self.set_in_space(name, id, vec);
+        Ok(())
+    }
+
+    pub fn get_vector(&self, id: usize, name: &str) -> Option<&[f32]> {
+        self.spaces.get(name)?.get_vector(id)
+    }
+
+    pub fn payload(&self, id: usize) -> Option<&Payload> {
+        self.payloads.get(&id)
+    }
+
+    pub fn set_payload(&mut self, id: usize, payload: Payload) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        self.payloads.insert(id, payload);
+        true
+    }
+
+    // Gone from every space at once
+    pub fn delete(&mut self, id: usize) -> bool {
+        if !self.points.remove(&id) {
+            return false;
+        }
+        for space in self.spaces.values_mut() {
+            space.delete(id);
+        }
+        self.payloads.remove(&id);
+        true
+    }
+
+    // (distance, id) closest first, only points that have a vector in that space
+    pub fn search(&self, name: &str, query: &[f32], k: usize, ef_search: Option<usize>) -> Result<Vec<(f32, usize)>, String> {
+        let space = self.space(name)?;
+        if query.len() != space.vectors.dim {
+            return Err(format!(
+                "Vector dimension mismatch in {}: expected {}, got {}",
+                name,
+                space.vectors.dim,
+                query.len()
+            ));
+        }
+        Ok(space.search(query, k, ef_search))
+    }
+
+    // Compacts every space, returns how many stale slots were dropped in total
+    pub fn compact(&mut self) -> Result<usize, String> {
+        let mut removed = 0;
+        for (name, space) in self.spaces.iter_mut() {
+            removed += space.compact().map_err(|e| format!("{}: {}", name, e))?;
+        }
+        Ok(removed)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, space) in &self.spaces {
+            space.validate().map_err(|e| format!("{}: {}", name, e))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+// Construction + search parameters for the index.
+// Set once through the builder, validated in build() and stored inside the .pho file
+// so a loaded index keeps inserting with the same settings it was built with.
+
+use crate::HNSW;
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    // squared L2, same numbers brute_force_search always returned
+    Euclidean,
+    // 1 - cos(a, b)
+    Cosine,
+    // -dot(a, b) so that smaller still means closer
+    InnerProduct,
+}
+
+impl Metric {
+    pub fn parse(name: &str) -> Result<Metric, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "euclidean" | "l2" => Ok(Metric::Euclidean),
+            "cosine" | "cos" => Ok(Metric::Cosine),
+            "inner_product" | "ip" | "dot" => Ok(Metric::InnerProduct),
+            other => Err(format!("Unknown metric: {}", other)),
+        }
+    }
+
+    pub fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Metric::Euclidean => a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum(),
+            Metric::Cosine => {
+                let (mut dot, mut na, mut nb) = (0.0f32, 0.0f32, 0.0f32);
+                for (x, y) in a.iter().zip(b) {
+                    dot += x * y;
+                    na += x * x;
+                    nb += y * y;
+                }
+                if na == 0.0 || nb == 0.0 {
+                    return 1.0;
+                }
+                1.0 - dot / (na.sqrt() * nb.sqrt())
+            }
+            Metric::InnerProduct => -a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>(),
+        }
+    }
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct HnswConfig {
+    pub dim: usize,
+    pub metric: Metric,
+    pub max_elements: usize,
+    // M: links created per insert
+    pub m: usize,
+    // max links kept on upper layers
+    pub m_max: usize,
+    // max links kept on the base layer (paper uses 2 * M)
+    pub m_max0: usize,
+    pub ef_construction: usize,
+    // default beam width for queries, can be overridden per call
+    pub ef_search: usize,
+    // level generation factor, 1 / ln(M) by default
+    pub m_l: f32,
+    // Some(seed) makes levels a pure function of (seed, node id) -> reproducible graphs
+    pub seed: Option<u64>,
+}
+
+impl HnswConfig {
+    pub fn new(dim: usize) -> Self {
+        let m = 16;
+        HnswConfig {
+            dim,
+            metric: Metric::Euclidean,
+            max_elements: 0,
+            m,
+            m_max: m,
+            m_max0: m * 2,
+            ef_construction: 64,
+            ef_search: 64,
+            m_l: 1.0 / (m as f32).ln(),
+            seed: None,
+        }
+    }
+
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = max_elements;
+        self
+    }
+
+    // Also resets m_max, m_max0 and m_l to the values derived from M,
+    // so call those setters after this one if you want to override them
+    pub fn m(mut self, m: usize) -> Self {
+        self.m = m;
+        self.m_max = m;
+        self.m_max0 = m * 2;
+        self.m_l = 1.0 / (m as f32).ln();
+        self
+    }
+
+    pub fn m_max(mut self, m_max: usize) -> Self {
+        self.m_max = m_max;
+        self
+    }
+
+    pub fn m_max0(mut self, m_max0: usize) -> Self {
+        self.m_max0 = m_max0;
+        self
+    }
+
+    pub fn ef_construction(mut self, ef_construction: usize) -> Self {
+        self.ef_construction = ef_construction;
+        self
+    }
+
+    pub fn ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search;
+        self
+    }
+
+    pub fn m_l(mut self, m_l: f32) -> Self {
+        self.m_l = m_l;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.dim == 0 {
+            return Err("dim must be greater than 0".to_string());
+        }
+        if self.m < 2 {
+            return Err(format!("m must be at least 2, got {}", self.m));
+        }
+        if self.m_max < self.m {
+            return Err(format!("m_max ({}) must be >= m ({})", self.m_max, self.m));
+        }
+        if self.m_max0 < self.m_max {
+            return Err(format!("m_max0 ({}) must be >= m_max ({})", self.m_max0, self.m_max));
+        }
+        if self.ef_construction == 0 {
+            return Err("ef_construction must be greater than 0".to_string());
+        }
+        if self.ef_search == 0 {
+            return Err("ef_search must be greater than 0".to_string());
+        }
+        if !self.m_l.is_finite() || self.m_l <= 0.0 {
+            return Err(format!("m_l must be a positive finite number, got {}", self.m_l));
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<HNSW, String> {
+        self.validate()?;
+        Ok(HNSW::from_config(self))
+    }
+}
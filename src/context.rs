@@ -0,0 +1,202 @@
+// RAG context assembly: search, merge hits that are next to each other in the same file,
+// then fill a token budget. Works off the payload fields written by ingest
+// ("text", "source", "start", "end"); hits without "text" are skipped, hits without
+// source/offsets are kept as standalone blocks.
+
+use crate::HNSW;
+use ordered_float::OrderedFloat;
+use std::cmp::min;
+
+// paragraph chunks are split on blank lines, so a few bytes of gap still means "next to each other"
+pub const MERGE_GAP: usize = 4;
+
+pub trait TokenCounter {
+    fn count(&self, text: &str) -> usize;
+}
+
+// Whitespace separated words, close enough when the real tokenizer isn't around
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WhitespaceTokens;
+
+impl TokenCounter for WhitespaceTokens {
+    fn count(&self, text: &str) -> usize {
+        text.split_whitespace().count()
+    }
+}
+
+// Any closure works too, e.g. |t: &str| tiktoken.encode(t).len()
+impl<F: Fn(&str) -> usize> TokenCounter for F {
+    fn count(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    // [n] marker used in the context string
+    pub index: usize,
+    pub ids: Vec<usize>,
+    pub source: Option<String>,
+    // byte range in the source covered by the context (after merging / truncation)
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    // best (smallest) distance among the merged hits
+    pub score: f32,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RetrievedContext {
+    pub text: String,
+    pub citations: Vec<Citation>,
+    pub tokens: usize,
+}
+
+struct Block {
+    ids: Vec<usize>,
+    source: Option<String>,
+    span: Option<(usize, usize)>,
+    text: String,
+    score: f32,
+    // (offset in text, source range) of every merged piece, to map truncation back to the file
+    segments: Vec<(usize, usize, usize)>,
+}
+
+impl Block {
+    // `other` starts at or after self
+    fn try_merge(&mut self, other: &Block) -> bool {
+        let (Some(source), Some((start, end)), Some((o_start, o_end))) = (&self.source, self.span, other.span) else {
+            return false;
+        };
+        if other.source.as_ref() != Some(source) || o_start > end + MERGE_GAP {
+            return false;
+        }
+        // spans come from payloads, only trust them when they describe the text byte for byte
+        // (a merged block already passed this for every piece)
+        if o_end - o_start != other.text.len() || (self.segments.len() == 1 && end - start != self.text.len()) {
+            return false;
+        }
+        if o_start < end && o_end > end && !other.text.is_char_boundary(end - o_start) {
+            return false;
+        }
+        if o_end > end {
+            if o_start >= end {
+                self.text.push_str("\n\n");
+                self.segments.push((self.text.len(), o_start, o_end));
+                self.text.push_str(&other.text);
+            } else {
+                // overlapping windows, only append the part we don't have yet
+                self.segments.push((self.text.len(), end, o_end));
+                self.text.push_str(&other.text[end - o_start..]);
+            }
+            self.span = Some((start, o_end));
+        }
+        self.ids.extend(&other.ids);
+        self.score = self.score.min(other.score);
+        true
+    }
+}
+
+// Longest word-boundary cut of text[from..] such that text[..cut] fits in budget tokens, 0 if none
+fn truncate_to(text: &str, from: usize, budget: usize, counter: &dyn TokenCounter) -> usize {
+    let cuts: Vec<usize> = text[from..]
+        .char_indices()
+        .filter(|&(i, c)| c.is_whitespace() && i > 0)
+        .map(|(i, _)| from + i)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    let (mut lo, mut hi) = (0, cuts.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if counter.count(&text[..cuts[mid]]) <= budget {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    if lo == 0 {
+        0
+    } else {
+        cuts[lo - 1]
+    }
+}
+
+impl HNSW {
+    // Top-k hits, merged per source, most relevant block first, each prefixed by "[n] ".
+    // A block that doesn't fit completely is cut at a word boundary and ends the context.
+    pub fn retrieve_context(
+        &self,
+        query: &[f32],
+        token_budget: usize,
+        k: usize,
+        counter: &dyn TokenCounter,
+    ) -> RetrievedContext {
+        let mut hits: Vec<Block> = self
+            .search(query, k, None)
+            .into_iter()
+            .filter_map(|(score, id)| {
+                let payload = self.payload(id)?;
+                let text = payload.get("text")?.as_str()?.to_string();
+                let source = payload.get("source").and_then(|v| v.as_str()).map(str::to_string);
+                let start = payload.get("start").and_then(|v| v.as_int());
+                let end = payload.get("end").and_then(|v| v.as_int());
+                let span = match (start, end) {
+                    (Some(s), Some(e)) if 0 <= s && s <= e => Some((s as usize, e as usize)),
+                    _ => None,
+                };
+                let segments = span.map(|(s, e)| vec![(0, s, e)]).unwrap_or_default();
+                Some(Block { ids: vec![id], source, span, text, score, segments })
+            })
+            .collect();
+
+        // same source next to each other, in file order, then merge neighbours
+        hits.sort_by(|a, b| (&a.source, a.span).cmp(&(&b.source, b.span)));
+        let mut blocks: Vec<Block> = Vec::new();
+        for hit in hits {
+            if !blocks.last_mut().is_some_and(|last| last.try_merge(&hit)) {
+                blocks.push(hit);
+            }
+        }
+        blocks.sort_by_key(|b| (OrderedFloat(b.score), b.ids[0]));
+
+        let mut context = RetrievedContext::default();
+        for block in blocks {
+            let index = context.citations.len() + 1;
+            let separator = if context.text.is_empty() { "" } else { "\n\n" };
+            let piece = format!("{}[{}] {}", separator, index, block.text);
+            let candidate = format!("{}{}", context.text, piece);
+            let used = counter.count(&candidate);
+
+            let mut citation = Citation {
+                index,
+                ids: block.ids,
+                source: block.source,
+                start: block.span.map(|s| s.0),
+                end: block.span.map(|s| s.1),
+                score: block.score,
+                truncated: false,
+            };
+            if used <= token_budget {
+                context.text = candidate;
+                context.tokens = used;
+                context.citations.push(citation);
+                continue;
+            }
+
+            let prefix_len = candidate.len() - block.text.len();
+            let cut = truncate_to(&candidate, prefix_len, token_budget, counter);
+            if cut > prefix_len {
+                let keep = cut - prefix_len;
+                context.text = candidate[..cut].to_string();
+                context.tokens = counter.count(&context.text);
+                if let Some(&(pos, start, end)) = block.segments.iter().rev().find(|s| s.0 < keep) {
+                    citation.end = Some(min(start + (keep - pos), end));
+                }
+                citation.truncated = true;
+                context.citations.push(citation);
+            }
+            break;
+        }
+        context
+    }
+}
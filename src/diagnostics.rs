@@ -0,0 +1,218 @@
+// Structural health check of the graph.
+// diagnostics() collects everything, validate() only fails on real corruption
+// (bad edges, oversized lists, store/graph mismatch), not on quality issues like unreachable nodes.
+
+use crate::HNSW;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayerDiagnostics {
+    pub layer: usize,
+    pub node_count: usize,
+    pub edge_count: usize,
+    // degree_histogram[d] = number of nodes with out-degree d
+    pub degree_histogram: Vec<usize>,
+    pub zero_in_degree: Vec<usize>,
+    // nodes in this layer that a BFS from the entry point never reaches
+    pub unreachable: Vec<usize>,
+    // (from, to) where `to` does not exist on this layer
+    pub dangling_edges: Vec<(usize, usize)>,
+    pub duplicate_edges: Vec<(usize, usize)>,
+    pub self_loops: Vec<usize>,
+    // nodes with more than m_max (m_max0 on layer 0) neighbors
+    pub over_capacity: Vec<usize>,
+    pub max_degree: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphDiagnostics {
+    pub vector_count: usize,
+    pub node_count: usize,
+    // soft deleted slots still taking space, compact() gets rid of them
+    pub deleted_count: usize,
+    pub entry_point: Option<usize>,
+    pub entry_level: usize,
+    // layer 0 first
+    pub layers: Vec<LayerDiagnostics>,
+}
+
+impl GraphDiagnostics {
+    // nodes no search can reach, i.e. unreachable on the base layer
+    pub fn unreachable(&self) -> &[usize] {
+        self.layers.first().map(|l| l.unreachable.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.vector_count != self.node_count {
+            errors.push(format!(
+                "vector store holds {} vectors but base layer has {} nodes",
+                self.vector_count, self.node_count
+            ));
+        }
+        match self.entry_point {
+            Some(ep) if ep >= self.node_count => errors.push(format!("entry point {} out of range", ep)),
+            Some(_) if self.entry_level != self.layers.len().saturating_sub(1) => errors.push(format!(
+                "entry point lives on {} upper layers but graph has {}",
+                self.entry_level,
+                self.layers.len().saturating_sub(1)
+            )),
+            None if self.node_count > 0 => errors.push("graph has nodes but no entry point".to_string()),
+            _ => {}
+        }
+        for l in &self.layers {
+            if !l.dangling_edges.is_empty() {
+                errors.push(format!("layer {}: {} dangling edges, e.g. {:?}", l.layer, l.dangling_edges.len(), l.dangling_edges[0]));
+            }
+            if !l.duplicate_edges.is_empty() {
+                errors.push(format!("layer {}: {} duplicate edges, e.g. {:?}", l.layer, l.duplicate_edges.len(), l.duplicate_edges[0]));
+            }
+            if !l.self_loops.is_empty() {
+                errors.push(format!("layer {}: {} self loops, e.g. node {}", l.layer, l.self_loops.len(), l.self_loops[0]));
+            }
+            if !l.over_capacity.is_empty() {
+                errors.push(format!("layer {}: {} nodes over M_max, e.g. node {}", l.layer, l.over_capacity.len(), l.over_capacity[0]));
+            }
+        }
+        errors
+    }
+}
+
+impl fmt::Display for GraphDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "HNSW Diagnostics:")?;
+        writeln!(
+            f,
+            "  Vectors: {}  Base Layer Nodes: {}  Deleted: {}",
+            self.vector_count, self.node_count, self.deleted_count
+        )?;
+        writeln!(f, "  Entry Point: {:?} (level {})", self.entry_point, self.entry_level)?;
+        writeln!(f, "  Unreachable From Entry Point: {}", self.unreachable().len())?;
+        for l in &self.layers {
+            writeln!(
+                f,
+                "  Layer {}: {} nodes, {} edges, max degree {}, zero in-degree {}, unreachable {}",
+                l.layer,
+                l.node_count,
+                l.edge_count,
+                l.max_degree,
+                l.zero_in_degree.len(),
+                l.unreachable.len()
+            )?;
+            let histogram: Vec<String> = l
+                .degree_histogram
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .map(|(degree, count)| format!("{}:{}", degree, count))
+                .collect();
+            writeln!(f, "    Degrees: {}", histogram.join(" "))?;
+        }
+        let errors = self.errors();
+        if errors.is_empty() {
+            write!(f, "  Status: OK")
+        } else {
+            writeln!(f, "  Status: CORRUPT")?;
+            for e in &errors {
+                writeln!(f, "    - {}", e)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl HNSW {
+    pub fn diagnostics(&self) -> GraphDiagnostics {
+        let mut layers = Vec::new();
+        for lc in 0..=self.layers.upper_layers.len() {
+            let nodes: Vec<usize> = if lc == 0 {
+                (0..self.layers.base_layer.len()).collect()
+            } else {
+                self.layers.upper_layers[lc - 1].keys().copied().collect()
+            };
+            layers.push(self.layer_diagnostics(lc, &nodes));
+        }
+
+        GraphDiagnostics {
+            vector_count: self.vectors.len(),
+            node_count: self.layers.base_layer.len(),
+            deleted_count: self.deleted.iter().filter(|d| **d).count(),
+            entry_point: self.entry_point,
+            entry_level: self.entry_point.map(|ep| self.layers.node_level(ep)).unwrap_or(0),
+            layers,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let errors = self.diagnostics().errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    fn layer_diagnostics(&self, lc: usize, nodes: &[usize]) -> LayerDiagnostics {
+        let in_layer = |n: usize| {
+            if lc == 0 {
+                n < self.layers.base_layer.len()
+            } else {
+                self.layers.upper_layers[lc - 1].contains_key(&n)
+            }
+        };
+        let m_max = if lc == 0 { self.config.m_max0 } else { self.config.m_max };
+
+        let mut diag = LayerDiagnostics {
+            layer: lc,
+            node_count: nodes.len(),
+            ..Default::default()
+        };
+        let mut has_in_edge: HashSet<usize> = HashSet::new();
+
+        for &node in nodes {
+            let neighbors = self.layers.get_neighbors(lc, node);
+            let degree = neighbors.len();
+            if diag.degree_histogram.len() <= degree {
+                diag.degree_histogram.resize(degree + 1, 0);
+            }
+            diag.degree_histogram[degree] += 1;
+            diag.edge_count += degree;
+            diag.max_degree = diag.max_degree.max(degree);
+            if degree > m_max {
+                diag.over_capacity.push(node);
+            }
+
+            let mut seen = HashSet::new();
+            for &n in neighbors {
+                if n == node {
+                    diag.self_loops.push(node);
+                } else if !in_layer(n) {
+                    diag.dangling_edges.push((node, n));
+                }
+                if !seen.insert(n) {
+                    diag.duplicate_edges.push((node, n));
+                }
+                has_in_edge.insert(n);
+            }
+        }
+
+        diag.zero_in_degree = nodes.iter().copied().filter(|n| !has_in_edge.contains(n)).collect();
+
+        // BFS from the entry point along this layer's edges
+        let mut reached = HashSet::new();
+        if let Some(ep) = self.entry_point.filter(|&ep| in_layer(ep)) {
+            let mut queue = VecDeque::from([ep]);
+            reached.insert(ep);
+            while let Some(node) = queue.pop_front() {
+                for &n in self.layers.get_neighbors(lc, node) {
+                    if in_layer(n) && reached.insert(n) {
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+        diag.unreachable = nodes.iter().copied().filter(|n| !reached.contains(n)).collect();
+        diag
+    }
+}
@@ -0,0 +1,399 @@
+// SSD-resident Vamana index. The file is:
+//
+//   sector 0        header, little endian u64s (see HEADER_FIELDS)
+//   sectors 1..     one record per node: dim f32 vector, u32 degree, r u32 neighbor slots.
+//                   Small records are packed several per sector but never straddle one,
+//                   big ones start on their own sector, so one node = one aligned read.
+//   after that      rkyv DiskMeta (PQ codebook + codes), sector aligned
+//
+// Only DiskMeta is loaded into RAM. Search walks the graph with PQ distances and reads the
+// block of every expanded node, the full vector in it gives the exact distance for the
+// final ranking and the neighbor list gives the next candidates.
+
+use crate::ivf::ProductQuantizer;
+use crate::vamana::VamanaIndex;
+use crate::{Metric, SearchStats};
+use memmap2::Mmap;
+use ordered_float::OrderedFloat;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use rkyv::rancor::Error;
+use rkyv::util::AlignedVec;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+pub const SECTOR: usize = 4096;
+const MAGIC: &[u8; 8] = b"PHOTODSK";
+const VERSION: u64 = 1;
+// magic, version, dim, r, n, medoid, node_len, meta_offset, meta_len
+const HEADER_FIELDS: usize = 9;
+// vectors used to train the PQ codebook
+const PQ_TRAIN_SAMPLE: usize = 20_000;
+const PQ_ITERATIONS: usize = 10;
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+pub struct DiskMeta {
+    pub metric: Metric,
+    pub l_search: usize,
+    pub pq: ProductQuantizer,
+    // pq.m bytes per node
+    pub codes: Vec<u8>,
+}
+
+fn node_len(dim: usize, r: usize) -> usize {
+    dim * 4 + 4 + r * 4
+}
+
+// byte offset of a node's record
+fn node_offset(node: usize, node_len: usize) -> usize {
+    if node_len <= SECTOR {
+        let per_sector = SECTOR / node_len;
+        SECTOR * (1 + node / per_sector) + (node % per_sector) * node_len
+    } else {
+        SECTOR * (1 + node * node_len.div_ceil(SECTOR))
+    }
+}
+
+fn align(len: usize) -> usize {
+    len.div_ceil(SECTOR) * SECTOR
+}
+
+fn io_err(path: &Path, e: std::io::Error) -> String {
+    format!("{}: {}", path.display(), e)
+}
+
+impl VamanaIndex {
+    // Writes the disk layout, trains the PQ codebook the disk index keeps in RAM
+    pub fn save_disk(&self, path: &Path) -> Result<(), String> {
+        let (dim, r, n) = (self.vectors.dim, self.config.r, self.len());
+        // neighbor slots are u32
+        if n > u32::MAX as usize {
+            return Err(format!("A disk index holds at most {} nodes, got {}", u32::MAX, n));
+        }
+        let mut rng = match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        let m = self.config.pq_m_for(dim);
+        let sample: Vec<f32> = rand::seq::index::sample(&mut rng, n, n.min(PQ_TRAIN_SAMPLE))
+            .into_iter()
+            .flat_map(|i| self.vectors.get(i).iter().copied())
+            .collect();
+        if sample.is_empty() {
+            return Err("Nothing to write, the index is empty".to_string());
+        }
+        let pq = ProductQuantizer::train(&sample, dim, m, PQ_ITERATIONS, &mut rng);
+        let codes: Vec<u8> = (0..n)
+            .into_par_iter()
+            .flat_map_iter(|row| {
+                let mut code = Vec::with_capacity(m);
+                pq.encode(self.vectors.get(row), &mut code);
+                code
+            })
+            .collect();
+        let meta = DiskMeta {
+            metric: self.vectors.metric,
+            l_search: self.config.l_search,
+            pq,
+            codes,
+        };
+        let meta = rkyv::to_bytes::<Error>(&meta).map_err(|e| e.to_string())?;
+
+        let node_len = node_len(dim, r);
+        let meta_offset = if n == 0 { SECTOR } else { align(node_offset(n - 1, node_len) + node_len) };
+        let file = File::create(path).map_err(|e| io_err(path, e))?;
+        let mut out = BufWriter::new(file);
+        let mut write = |bytes: &[u8]| out.write_all(bytes).map_err(|e| io_err(path, e));
+
+        let mut header = Vec::with_capacity(SECTOR);
+        header.extend_from_slice(MAGIC);
+        for field in [VERSION, dim as u64, r as u64, n as u64, self.medoid as u64, node_len as u64, meta_offset as u64, meta.len() as u64] {
+            header.extend_from_slice(&field.to_le_bytes());
+        }
+        debug_assert_eq!(header.len(), HEADER_FIELDS * 8);
+        header.resize(SECTOR, 0);
+        write(&header)?;
+
+        let mut pos = SECTOR;
+        let mut record = Vec::with_capacity(node_len);
+        for node in 0..n {
+            let offset = node_offset(node, node_len);
+            write(&vec![0u8; offset - pos])?;
+            record.clear();
+            for x in self.vectors.get(node) {
+                record.extend_from_slice(&x.to_le_bytes());
+            }
+            let neighbors = &self.neighbors[node];
+            record.extend_from_slice(&(neighbors.len() as u32).to_le_bytes());
+            for &nb in neighbors {
+                record.extend_from_slice(&(nb as u32).to_le_bytes());
+            }
+            record.resize(node_len, 0);
+            write(&record)?;
+            pos = offset + node_len;
+        }
+        write(&vec![0u8; meta_offset - pos])?;
+        write(&meta)?;
+        write(&vec![0u8; align(meta.len()) - meta.len()])?;
+        out.flush().map_err(|e| io_err(path, e))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadMode {
+    // blocks are slices of a read-only mapping, the kernel pages them in
+    Mmap,
+    // one pread per block into a buffer, no mapping at all
+    Pread,
+}
+
+#[derive(Debug)]
+enum Blocks {
+    Mmap(Mmap),
+    Pread(File),
+}
+
+#[derive(Debug)]
+pub struct DiskIndex {
+    pub path: PathBuf,
+    blocks: Blocks,
+    pub dim: usize,
+    pub r: usize,
+    pub n: usize,
+    pub medoid: usize,
+    node_len: usize,
+    pub meta: DiskMeta,
+    // candidate list size, defaults to the one the graph was built with
+    pub l_search: usize,
+    // blocks read per hop
+    pub beam_width: usize,
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset)? {
+            0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            read => {
+                buf = &mut buf[read..];
+                offset += read as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl DiskIndex {
+    pub fn open(path: &Path, mode: ReadMode) -> Result<DiskIndex, String> {
+        let file = File::open(path).map_err(|e| io_err(path, e))?;
+        let mut header = [0u8; HEADER_FIELDS * 8];
+        read_at(&file, &mut header, 0).map_err(|e| io_err(path, e))?;
+        if &header[..8] != MAGIC {
+            return Err(format!("{} is not a disk index", path.display()));
+        }
+        let field = |i: usize| u64::from_le_bytes(header[i * 8..(i + 1) * 8].try_into().unwrap()) as usize;
+        if field(1) as u64 != VERSION {
+            return Err(format!("Unsupported disk index version: {}", field(1)));
+        }
+        let (dim, r, n, medoid, node_len, meta_offset, meta_len) =
+            (field(2), field(3), field(4), field(5), field(6), field(7), field(8));
+        let file_len = file.metadata().map_err(|e| io_err(path, e))?.len() as usize;
+        // records never overlap, so n of them can't take more than the whole file.
+        // dim / r come straight from the file, node_len() must not see values that overflow
+        if dim == 0
+            || dim > file_len
+            || r > file_len
+            || node_len != self::node_len(dim, r)
+            || (n > 0 && medoid >= n)
+            || n.checked_mul(node_len).is_none_or(|bytes| bytes > file_len)
+            // bounds node_offset(n - 1) + node_len, so computing it below can't overflow
+            || n.checked_mul(node_len.saturating_add(SECTOR)).and_then(|b| b.checked_add(2 * SECTOR)).is_none()
+        {
+            return Err("Corrupted disk index header".to_string());
+        }
+        let nodes_end = if n == 0 { SECTOR } else { node_offset(n - 1, node_len) + node_len };
+        if meta_offset < nodes_end || meta_offset.checked_add(meta_len).is_none_or(|end| end > file_len) {
+            return Err("Corrupted disk index header: metadata overlaps the nodes or runs past the file".to_string());
+        }
+
+        let mut meta_bytes = vec![0u8; meta_len];
+        read_at(&file, &mut meta_bytes, meta_offset as u64).map_err(|e| io_err(path, e))?;
+        let mut aligned = AlignedVec::<16>::with_capacity(meta_len);
+        aligned.extend_from_slice(&meta_bytes);
+        let meta = rkyv::from_bytes::<DiskMeta, Error>(&aligned).map_err(|e| e.to_string())?;
+        meta.pq.validate(dim)?;
+        if Some(meta.codes.len()) != n.checked_mul(meta.pq.m) {
+            return Err("Disk index code length mismatch".to_string());
+        }
+        if meta.codes.iter().any(|&c| c as usize >= meta.pq.ksub) {
+            return Err("Disk index has PQ codes past the codebook".to_string());
+        }
+
+        let blocks = match mode {
+            ReadMode::Pread => Blocks::Pread(file),
+            ReadMode::Mmap => {
+                // the file is opened read only and never written while mapped
+                let mmap = unsafe { Mmap::map(&file) }.map_err(|e| io_err(path, e))?;
+                // graph hops jump all over the file, read-ahead would only waste IO
+                #[cfg(unix)]
+                mmap.advise(memmap2::Advice::Random).map_err(|e| io_err(path, e))?;
+                Blocks::Mmap(mmap)
+            }
+        };
+        Ok(DiskIndex {
+            path: path.to_path_buf(),
+            blocks,
+            dim,
+            r,
+            n,
+            medoid,
+            node_len,
+            l_search: meta.l_search,
+            meta,
+            beam_width: 4,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    // (full vector, neighbors) from the node's block
+    pub fn read_node(&self, node: usize) -> Result<(Vec<f32>, Vec<usize>), String> {
+        if node >= self.n {
+            return Err(format!("Unknown node: {}", node));
+        }
+        let offset = node_offset(node, self.node_len);
+        let mut buf;
+        let block = match &self.blocks {
+            Blocks::Mmap(mmap) => &mmap[offset..offset + self.node_len],
+            Blocks::Pread(file) => {
+                buf = vec![0u8; self.node_len];
+                read_at(file, &mut buf, offset as u64).map_err(|e| io_err(&self.path, e))?;
+                &buf[..]
+            }
+        };
+        let word = |i: usize| block[i * 4..(i + 1) * 4].try_into().unwrap();
+        let vector = (0..self.dim).map(|i| f32::from_le_bytes(word(i))).collect();
+        let degree = u32::from_le_bytes(word(self.dim)) as usize;
+        if degree > self.r {
+            return Err(format!("Corrupted block for node {}", node));
+        }
+        let neighbors: Vec<usize> = (0..degree).map(|i| u32::from_le_bytes(word(self.dim + 1 + i)) as usize).collect();
+        if neighbors.iter().any(|&nb| nb >= self.n) {
+            return Err(format!("Corrupted block for node {}", node));
+        }
+        Ok((vector, neighbors))
+    }
+
+    fn pq_distance(&self, table: &[f32], query_norm: f32, node: usize) -> f32 {
+        let m = self.meta.pq.m;
+        self.meta.pq.distance(self.meta.metric, table, query_norm, &self.meta.codes[node * m..(node + 1) * m])
+    }
+
+    pub fn search(&self, query: &[f32], k: usize, l_search: Option<usize>) -> Result<Vec<(f32, usize)>, String> {
+        Ok(self.search_with_stats(query, k, l_search)?.0)
+    }
+
+    // Beam search: the beam_width closest unexpanded candidates (by PQ distance) get their
+    // blocks read per hop. stats.hops counts blocks read, distance_evaluations the exact ones.
+    pub fn search_with_stats(&self, query: &[f32], k: usize, l_search: Option<usize>) -> Result<(Vec<(f32, usize)>, SearchStats), String> {
+        let start = Instant::now();
+        let mut stats = SearchStats::default();
+        if query.len() != self.dim {
+            return Err(format!("Vector dimension mismatch: expected {}, got {}", self.dim, query.len()));
+        }
+        if self.is_empty() {
+            return Ok((Vec::new(), stats));
+        }
+        let l = l_search.unwrap_or(self.l_search).max(k);
+        let table = self.meta.pq.table(query);
+        let query_norm = query.iter().map(|x| x * x).sum();
+
+        let mut seen = HashSet::from([self.medoid]);
+        // (pq distance, node, expanded), sorted
+        let mut list = vec![(OrderedFloat(self.pq_distance(&table, query_norm, self.medoid)), self.medoid, false)];
+        let mut exact = Vec::new();
+        loop {
+            let beam: Vec<usize> = list
+                .iter_mut()
+                .filter(|(_, _, done)| !done)
+                .take(self.beam_width)
+                .map(|c| {
+                    c.2 = true;
+                    c.1
+                })
+                .collect();
+            if beam.is_empty() {
+                break;
+            }
+            for node in beam {
+                let (vector, neighbors) = self.read_node(node)?;
+                stats.hops += 1;
+                stats.distance_evaluations += 1;
+                exact.push((OrderedFloat(self.meta.metric.distance(query, &vector)), node));
+                for nb in neighbors {
+                    if !seen.insert(nb) {
+                        continue;
+                    }
+                    let d = OrderedFloat(self.pq_distance(&table, query_norm, nb));
+                    if list.len() < l || d < list[list.len() - 1].0 {
+                        let at = list.partition_point(|&(x, _, _)| x <= d);
+                        list.insert(at, (d, nb, false));
+                        list.truncate(l);
+                    }
+                }
+            }
+        }
+        exact.sort_unstable();
+        exact.truncate(k);
+        stats.elapsed = start.elapsed();
+        Ok((exact.into_iter().map(|(OrderedFloat(d), node)| (d, node)).collect(), stats))
+    }
+
+    // Evicts this file from the page cache, including pages our own mapping holds,
+    // so the next searches really go to the disk
+    pub fn drop_page_cache(&self) -> Result<(), String> {
+        #[cfg(unix)]
+        if let Blocks::Mmap(mmap) = &self.blocks {
+            // read-only file mapping, dropped pages are just faulted in again from the file
+            unsafe { mmap.unchecked_advise(memmap2::UncheckedAdvice::DontNeed) }.map_err(|e| io_err(&self.path, e))?;
+        }
+        drop_page_cache(&self.path)
+    }
+}
+
+// Cold-cache harness for benchmarks / tests: flushes the file and asks the kernel to drop
+// its cached pages. Needs no root, unlike writing to /proc/sys/vm/drop_caches.
+#[cfg(target_os = "linux")]
+pub fn drop_page_cache(path: &Path) -> Result<(), String> {
+    use std::os::unix::io::AsRawFd;
+    let file = File::open(path).map_err(|e| io_err(path, e))?;
+    file.sync_all().map_err(|e| io_err(path, e))?;
+    let ret = unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) };
+    if ret != 0 {
+        return Err(io_err(path, std::io::Error::from_raw_os_error(ret)));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn drop_page_cache(_path: &Path) -> Result<(), String> {
+    Err("Dropping the page cache is only supported on linux".to_string())
+}
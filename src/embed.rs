@@ -0,0 +1,163 @@
+// Text -> vector, so PhotonDB can take and answer plain text without an external model.
+// Two offline embedders ship with the crate:
+// HashingEmbedder: words + character n-grams hashed into `dim` buckets (no training, no files)
+// WordVectors: average of static word vectors from a GloVe / word2vec text file
+// Anything else (a Python model, an HTTP API, ...) just implements Embedder.
+
+use crate::text::tokenize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+pub trait Embedder: Send + Sync {
+    fn dim(&self) -> usize;
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String>;
+
+    fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, String> {
+        texts.iter().map(|t| self.embed(t)).collect()
+    }
+}
+
+fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+// FNV-1a, std's hasher is randomly seeded per process and the vectors end up in .pho files
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HashingEmbedder {
+    pub dim: usize,
+    // character n-gram sizes, taken from "<word>" so prefixes/suffixes get their own grams
+    pub min_n: usize,
+    pub max_n: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dim: usize) -> Self {
+        HashingEmbedder { dim, min_n: 3, max_n: 5 }
+    }
+
+    pub fn ngrams(mut self, min_n: usize, max_n: usize) -> Self {
+        self.min_n = min_n;
+        self.max_n = max_n;
+        self
+    }
+
+    fn add(&self, v: &mut [f32], feature: &str) {
+        let hash = fnv1a(feature.as_bytes());
+        // top bit picks the sign so collisions cancel out on average
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        v[(hash % self.dim as u64) as usize] += sign;
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    // L2 normalized, so cosine and euclidean rank the same way
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+        if self.dim == 0 || self.min_n == 0 || self.min_n > self.max_n {
+            return Err(format!(
+                "Invalid hashing embedder: dim {}, ngrams {}..={}",
+                self.dim, self.min_n, self.max_n
+            ));
+        }
+        let mut v = vec![0.0; self.dim];
+        for word in tokenize(text) {
+            self.add(&mut v, &word);
+            let chars: Vec<char> = format!("<{}>", word).chars().collect();
+            for n in self.min_n..=self.max_n {
+                for gram in chars.windows(n) {
+                    self.add(&mut v, &gram.iter().collect::<String>());
+                }
+            }
+        }
+        normalize(&mut v);
+        Ok(v)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordVectors {
+    pub dim: usize,
+    // lowercased word -> vector
+    pub vectors: HashMap<String, Vec<f32>>,
+}
+
+impl WordVectors {
+    // "word x1 x2 ... xd" per line. GloVe files have no header, word2vec text files start
+    // with "count dim", which is skipped. First occurrence wins after lowercasing.
+    pub fn load(path: &Path) -> Result<WordVectors, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut dim = 0;
+        let mut vectors = HashMap::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let mut parts = line.split_whitespace();
+            let Some(word) = parts.next() else {
+                continue;
+            };
+            let values: Vec<f32> = parts
+                .map(|x| x.parse::<f32>())
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))?;
+            if i == 0 && values.len() == 1 {
+                continue;
+            }
+            if dim == 0 {
+                dim = values.len();
+            }
+            if values.len() != dim {
+                return Err(format!(
+                    "{}:{}: expected {} values, got {}",
+                    path.display(),
+                    i + 1,
+                    dim,
+                    values.len()
+                ));
+            }
+            vectors.entry(word.to_lowercase()).or_insert(values);
+        }
+        if vectors.is_empty() {
+            return Err(format!("{}: no word vectors found", path.display()));
+        }
+        Ok(WordVectors { dim, vectors })
+    }
+}
+
+impl Embedder for WordVectors {
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    // Mean of the known words. No known words -> zero vector (cosine treats it as unrelated to everything)
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+        let mut sum = vec![0.0; self.dim];
+        let mut count = 0;
+        for word in tokenize(text) {
+            if let Some(v) = self.vectors.get(&word) {
+                sum.iter_mut().zip(v).for_each(|(s, x)| *s += x);
+                count += 1;
+            }
+        }
+        if count > 0 {
+            sum.iter_mut().for_each(|s| *s /= count as f32);
+        }
+        Ok(sum)
+    }
+}
@@ -0,0 +1,61 @@
+// Dump stored vectors for migration / auditing.
+// .npy  -> float32 array of shape (n, dim), numpy.load() reads it directly
+// .fvecs -> the TEXMEX format (i32 dim, then dim f32 per row, little endian) used by SIFT & co.
+// Rows are live points in external id order, the ids come back so callers can line them up.
+
+use crate::HNSW;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+impl HNSW {
+    pub fn export_npy(&self, path: &Path) -> Result<Vec<usize>, String> {
+        let mut out = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        let ids: Vec<usize> = self.slots.keys().copied().collect();
+
+        let mut header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {}), }}",
+            ids.len(),
+            self.vectors.dim
+        );
+        // magic (6) + version (2) + header len (2) + header must be a multiple of 64, ending in \n
+        let unpadded = 10 + header.len() + 1;
+        header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+        header.push('\n');
+
+        out.write_all(b"\x93NUMPY\x01\x00").map_err(|e| e.to_string())?;
+        out.write_all(&(header.len() as u16).to_le_bytes()).map_err(|e| e.to_string())?;
+        out.write_all(header.as_bytes()).map_err(|e| e.to_string())?;
+        for (_, vector, _) in self.iter() {
+            for x in vector {
+                out.write_all(&x.to_le_bytes()).map_err(|e| e.to_string())?;
+            }
+        }
+        out.flush().map_err(|e| e.to_string())?;
+        Ok(ids)
+    }
+
+    pub fn export_fvecs(&self, path: &Path) -> Result<Vec<usize>, String> {
+        let mut out = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        let dim = (self.vectors.dim as i32).to_le_bytes();
+        let mut ids = Vec::with_capacity(self.len());
+        for (id, vector, _) in self.iter() {
+            out.write_all(&dim).map_err(|e| e.to_string())?;
+            for x in vector {
+                out.write_all(&x.to_le_bytes()).map_err(|e| e.to_string())?;
+            }
+            ids.push(id);
+        }
+        out.flush().map_err(|e| e.to_string())?;
+        Ok(ids)
+    }
+
+    // Picks the format from the file extension
+    pub fn export(&self, path: &Path) -> Result<Vec<usize>, String> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("npy") => self.export_npy(path),
+            Some("fvecs") => self.export_fvecs(path),
+            other => Err(format!("Unsupported export format: {:?}, use .npy or .fvecs", other)),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// Exact search with no graph at all: every query scans every vector.
+// For small collections this is as fast as HNSW, has perfect recall and nothing to tune.
+// Deletes are physical (last row moves into the hole) since there are no edges to keep valid.
+
+use crate::{Metric, VectorStore};
+use ordered_float::OrderedFloat;
+use rayon::prelude::*;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+pub struct FlatIndex {
+    pub vectors: VectorStore,
+    pub ids: Vec<usize>,              // row -> external id
+    pub rows: BTreeMap<usize, usize>, // external id -> row
+    pub next_id: usize,
+}
+
+impl FlatIndex {
+    pub fn new(dim: usize, metric: Metric) -> Self {
+        FlatIndex {
+            vectors: VectorStore::with_metric(0, dim, metric),
+            ids: Vec::new(),
+            rows: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn insert(&mut self, vec: &[f32]) -> usize {
+        assert_eq!(vec.len(), self.vectors.dim, "Vector dimension mismatch");
+        let id = self.next_id;
+        self.next_id += 1;
+        let row = self.vectors.insert(vec);
+        self.ids.push(id);
+        self.rows.insert(id, row);
+        id
+    }
+
+    pub fn delete(&mut self, id: usize) -> bool {
+        let Some(row) = self.rows.remove(&id) else {
+            return false;
+        };
+        let last = self.ids.len() - 1;
+        self.vectors.swap_remove(row);
+        if row != last {
+            self.ids[row] = self.ids[last];
+            self.rows.insert(self.ids[row], row);
+        }
+        self.ids.pop();
+        true
+    }
+
+    // Checks a loaded index is consistent, so delete / search can't index out of bounds
+    pub fn validate(&self) -> Result<(), String> {
+        if self.vectors.dim == 0 {
+            return Err("dim must be greater than 0".to_string());
+        }
+        if self.vectors.is_tiered() {
+            return Err("A flat index deletes in place, its vectors can't be tiered".to_string());
+        }
+        if self.vectors.len() != self.ids.len() || self.rows.len() != self.ids.len() {
+            return Err(format!(
+                "Store has {} vectors, {} ids and {} rows",
+                self.vectors.len(),
+                self.ids.len(),
+                self.rows.len()
+            ));
+        }
+        for (&id, &row) in &self.rows {
+            if self.ids.get(row) != Some(&id) {
+                return Err(format!("Id {} points at row {}, which holds a different id", id, row));
+            }
+            if id >= self.next_id {
+                return Err(format!("Id {} is not below next_id {}", id, self.next_id));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_vector(&self, id: usize) -> Option<&[f32]> {
+        self.rows.get(&id).map(|&row| self.vectors.get(row))
+    }
+
+    // Exact top-k, (distance, id) closest first
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        let mut results: Vec<(OrderedFloat<f32>, usize)> = (0..self.ids.len())
+            .into_par_iter()
+            .map(|row| (OrderedFloat(self.vectors.distance_to_query(row, query)), self.ids[row]))
+            .collect();
+        if k < results.len() {
+            results.select_nth_unstable(k);
+            results.truncate(k);
+        }
+        results.sort_unstable();
+        results.into_iter().map(|(OrderedFloat(d), id)| (d, id)).collect()
+    }
+}
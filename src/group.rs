@@ -0,0 +1,42 @@
+// Parent-document grouping: at most `per_group` hits per distinct value of a payload field,
+// so five chunks of the same document don't crowd out everything else.
+// The graph beam is doubled until enough groups show up or it covers the whole index.
+
+use crate::{PayloadValue, HNSW};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub key: PayloadValue,
+    // (distance, id), closest first
+    pub hits: Vec<(f32, usize)>,
+}
+
+impl HNSW {
+    // Groups come ordered by their best hit. Points without `group_by` are ignored.
+    pub fn search_grouped(&self, query: &[f32], group_by: &str, groups: usize, per_group: usize) -> Vec<Group> {
+        if groups == 0 || per_group == 0 {
+            return Vec::new();
+        }
+        let mut ef = self.config.ef_search.max(groups * per_group);
+        loop {
+            let mut result: Vec<Group> = Vec::new();
+            for (dist, id) in self.candidates(query, ef) {
+                let Some(key) = self.payload(id).and_then(|p| p.get(group_by)) else {
+                    continue;
+                };
+                // linear scan, there are only `groups` of them and PayloadValue has floats in it
+                match result.iter().position(|g| &g.key == key) {
+                    Some(i) if result[i].hits.len() < per_group => result[i].hits.push((dist, id)),
+                    Some(_) => {}
+                    None if result.len() < groups => result.push(Group { key: key.clone(), hits: vec![(dist, id)] }),
+                    None => {}
+                }
+            }
+            // the beam already holds every stored slot, searching wider won't find more
+            if result.len() >= groups || ef >= self.vectors.len() {
+                return result;
+            }
+            ef *= 2;
+        }
+    }
+}
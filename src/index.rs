@@ -0,0 +1,439 @@
+// Common interface over the index types, so PhotonDB / PyHNSW can hold either one.
+// Everything graph specific (payloads, text, diagnostics, ...) stays on HNSW itself,
+// AnyIndex::as_hnsw() gets at it.
+//
+// File layout written by every Index::save: 8 byte magic, 1 byte index kind, 7 bytes padding,
+// then the rkyv bytes of that index. Files without the magic are plain HNSW from before.
+
+use crate::{FlatIndex, IvfIndex, HNSW};
+use rayon::prelude::*;
+use rkyv::rancor::Error;
+use rkyv::util::AlignedVec;
+use std::fs;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"PHOTONIX";
+const HEADER_LEN: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexKind {
+    #[default]
+    Hnsw,
+    Flat,
+    Ivf,
+}
+
+impl IndexKind {
+    pub fn parse(name: &str) -> Result<IndexKind, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "hnsw" => Ok(IndexKind::Hnsw),
+            "flat" | "exact" => Ok(IndexKind::Flat),
+            "ivf" => Ok(IndexKind::Ivf),
+            other => Err(format!("Unknown index type: {}", other)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexKind::Hnsw => "hnsw",
+            IndexKind::Flat => "flat",
+            IndexKind::Ivf => "ivf",
+        }
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            IndexKind::Hnsw => 0,
+            IndexKind::Flat => 1,
+            IndexKind::Ivf => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<IndexKind, String> {
+        match tag {
+            0 => Ok(IndexKind::Hnsw),
+            1 => Ok(IndexKind::Flat),
+            2 => Ok(IndexKind::Ivf),
+            other => Err(format!("Unknown index type tag in file: {}", other)),
+        }
+    }
+}
+
+pub trait Index: Send + Sync {
+    fn kind(&self) -> IndexKind;
+    fn dim(&self) -> usize;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert(&mut self, vec: &[f32]) -> usize;
+    fn delete(&mut self, id: usize) -> bool;
+    fn get_vector(&self, id: usize) -> Option<&[f32]>;
+
+    // (distance, id) closest first, default search settings of the index
+    fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)>;
+
+    // queries is a flat buffer of n * dim floats, one result list per query
+    fn search_batch(&self, queries: &[f32], k: usize) -> Vec<Vec<(f32, usize)>> {
+        assert_eq!(queries.len() % self.dim(), 0, "Query buffer length must be a multiple of dim");
+        queries.par_chunks_exact(self.dim()).map(|q| self.search(q, k)).collect()
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String>;
+
+    fn load(path: &Path) -> Result<Self, String>
+    where
+        Self: Sized;
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    fs::write(path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn with_header(kind: IndexKind, body: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + body.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(kind.tag());
+    bytes.resize(HEADER_LEN, 0);
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+// (kind, rkyv bytes), aligned the way rkyv wants them
+fn split_header(bytes: &[u8]) -> Result<(IndexKind, AlignedVec<16>), String> {
+    let (kind, body) = if !bytes.starts_with(MAGIC) {
+        (IndexKind::Hnsw, bytes)
+    } else if bytes.len() < HEADER_LEN {
+        return Err("Truncated index file".to_string());
+    } else {
+        (IndexKind::from_tag(bytes[MAGIC.len()])?, &bytes[HEADER_LEN..])
+    };
+    let mut aligned = AlignedVec::<16>::with_capacity(body.len());
+    aligned.extend_from_slice(body);
+    Ok((kind, aligned))
+}
+
+// Index::load of one type on a file holding another
+fn check_kind(path: &Path, found: IndexKind, expected: IndexKind) -> Result<(), String> {
+    if found != expected {
+        return Err(format!("{} holds a {} index, not {}", path.display(), found.name(), expected.name()));
+    }
+    Ok(())
+}
+
+fn hnsw_from(body: &[u8]) -> Result<HNSW, String> {
+    let mut hnsw = rkyv::from_bytes::<HNSW, Error>(body).map_err(|e| e.to_string())?;
+    // a tiered store maps its vector file again
+    hnsw.vectors.attach()?;
+    hnsw.validate()?;
+    Ok(hnsw)
+}
+
+fn flat_from(body: &[u8]) -> Result<FlatIndex, String> {
+    let flat = rkyv::from_bytes::<FlatIndex, Error>(body).map_err(|e| e.to_string())?;
+    flat.validate()?;
+    Ok(flat)
+}
+
+fn ivf_from(body: &[u8]) -> Result<IvfIndex, String> {
+    let ivf = rkyv::from_bytes::<IvfIndex, Error>(body).map_err(|e| e.to_string())?;
+    ivf.validate()?;
+    Ok(ivf)
+}
+
+impl Index for HNSW {
+    fn kind(&self) -> IndexKind {
+        IndexKind::Hnsw
+    }
+
+    fn dim(&self) -> usize {
+        self.vectors.dim
+    }
+
+    fn len(&self) -> usize {
+        HNSW::len(self)
+    }
+
+    fn insert(&mut self, vec: &[f32]) -> usize {
+        HNSW::insert(self, vec)
+    }
+
+    fn delete(&mut self, id: usize) -> bool {
+        HNSW::delete(self, id)
+    }
+
+    fn get_vector(&self, id: usize) -> Option<&[f32]> {
+        HNSW::get_vector(self, id)
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        HNSW::search(self, query, k, None)
+    }
+
+    fn search_batch(&self, queries: &[f32], k: usize) -> Vec<Vec<(f32, usize)>> {
+        HNSW::search_batch(self, queries, k, None)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let body = rkyv::to_bytes::<Error>(self).map_err(|e| e.to_string())?;
+        write(path, &with_header(self.kind(), &body))
+    }
+
+    // also takes the headerless files PyHNSW.save used to write
+    fn load(path: &Path) -> Result<Self, String> {
+        let (kind, body) = split_header(&read(path)?)?;
+        check_kind(path, kind, IndexKind::Hnsw)?;
+        hnsw_from(&body)
+    }
+}
+
+impl Index for FlatIndex {
+    fn kind(&self) -> IndexKind {
+        IndexKind::Flat
+    }
+
+    fn dim(&self) -> usize {
+        self.vectors.dim
+    }
+
+    fn len(&self) -> usize {
+        FlatIndex::len(self)
+    }
+
+    fn insert(&mut self, vec: &[f32]) -> usize {
+        FlatIndex::insert(self, vec)
+    }
+
+    fn delete(&mut self, id: usize) -> bool {
+        FlatIndex::delete(self, id)
+    }
+
+    fn get_vector(&self, id: usize) -> Option<&[f32]> {
+        FlatIndex::get_vector(self, id)
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        FlatIndex::search(self, query, k)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let body = rkyv::to_bytes::<Error>(self).map_err(|e| e.to_string())?;
+        write(path, &with_header(self.kind(), &body))
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let (kind, body) = split_header(&read(path)?)?;
+        check_kind(path, kind, IndexKind::Flat)?;
+        flat_from(&body)
+    }
+}
+
+impl Index for IvfIndex {
+    fn kind(&self) -> IndexKind {
+        IndexKind::Ivf
+    }
+
+    fn dim(&self) -> usize {
+        self.config.dim
+    }
+
+    fn len(&self) -> usize {
+        IvfIndex::len(self)
+    }
+
+    fn insert(&mut self, vec: &[f32]) -> usize {
+        IvfIndex::insert(self, vec)
+    }
+
+    fn delete(&mut self, id: usize) -> bool {
+        IvfIndex::delete(self, id)
+    }
+
+    fn get_vector(&self, id: usize) -> Option<&[f32]> {
+        IvfIndex::get_vector(self, id)
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        IvfIndex::search(self, query, k)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let body = rkyv::to_bytes::<Error>(self).map_err(|e| e.to_string())?;
+        write(path, &with_header(self.kind(), &body))
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let (kind, body) = split_header(&read(path)?)?;
+        check_kind(path, kind, IndexKind::Ivf)?;
+        ivf_from(&body)
+    }
+}
+
+// Whichever index a file / collection was created with.
+// There's one of these per database, the size gap between variants doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq)]
+pub enum AnyIndex {
+    Hnsw(HNSW),
+    Flat(FlatIndex),
+    Ivf(IvfIndex),
+}
+
+impl AnyIndex {
+    pub fn as_hnsw(&self) -> Option<&HNSW> {
+        match self {
+            AnyIndex::Hnsw(hnsw) => Some(hnsw),
+            _ => None,
+        }
+    }
+
+    pub fn as_hnsw_mut(&mut self) -> Option<&mut HNSW> {
+        match self {
+            AnyIndex::Hnsw(hnsw) => Some(hnsw),
+            _ => None,
+        }
+    }
+
+    pub fn as_ivf(&self) -> Option<&IvfIndex> {
+        match self {
+            AnyIndex::Ivf(ivf) => Some(ivf),
+            _ => None,
+        }
+    }
+
+    pub fn as_ivf_mut(&mut self) -> Option<&mut IvfIndex> {
+        match self {
+            AnyIndex::Ivf(ivf) => Some(ivf),
+            _ => None,
+        }
+    }
+
+    // depth is ef_search for HNSW and nprobe for IVF, a flat index is always exact
+    pub fn search_with_depth(&self, query: &[f32], k: usize, depth: Option<usize>) -> Vec<(f32, usize)> {
+        match self {
+            AnyIndex::Hnsw(hnsw) => hnsw.search(query, k, depth),
+            AnyIndex::Flat(flat) => flat.search(query, k),
+            AnyIndex::Ivf(ivf) => ivf.search_with_nprobe(query, k, depth),
+        }
+    }
+
+    pub fn search_batch_with_depth(&self, queries: &[f32], k: usize, depth: Option<usize>) -> Vec<Vec<(f32, usize)>> {
+        assert_eq!(queries.len() % self.dim(), 0, "Query buffer length must be a multiple of dim");
+        queries
+            .par_chunks_exact(self.dim())
+            .map(|q| self.search_with_depth(q, k, depth))
+            .collect()
+    }
+
+    // Exact top-k whatever the index, for measuring recall
+    pub fn brute_force_search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        match self {
+            AnyIndex::Hnsw(hnsw) => hnsw.brute_force_search(query, k),
+            AnyIndex::Flat(flat) => flat.search(query, k),
+            AnyIndex::Ivf(ivf) => ivf.brute_force_search(query, k),
+        }
+    }
+
+    fn inner(&self) -> &dyn Index {
+        match self {
+            AnyIndex::Hnsw(hnsw) => hnsw,
+            AnyIndex::Flat(flat) => flat,
+            AnyIndex::Ivf(ivf) => ivf,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn Index {
+        match self {
+            AnyIndex::Hnsw(hnsw) => hnsw,
+            AnyIndex::Flat(flat) => flat,
+            AnyIndex::Ivf(ivf) => ivf,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let body = match self {
+            AnyIndex::Hnsw(hnsw) => rkyv::to_bytes::<Error>(hnsw),
+            AnyIndex::Flat(flat) => rkyv::to_bytes::<Error>(flat),
+            AnyIndex::Ivf(ivf) => rkyv::to_bytes::<Error>(ivf),
+        }
+        .map_err(|e| e.to_string())?;
+        Ok(with_header(self.kind(), &body))
+    }
+
+    // the same bytes Index::save writes for any of the types, or a headerless HNSW
+    pub fn from_bytes(bytes: &[u8]) -> Result<AnyIndex, String> {
+        let (kind, body) = split_header(bytes)?;
+        match kind {
+            IndexKind::Hnsw => hnsw_from(&body).map(AnyIndex::Hnsw),
+            IndexKind::Flat => flat_from(&body).map(AnyIndex::Flat),
+            IndexKind::Ivf => ivf_from(&body).map(AnyIndex::Ivf),
+        }
+    }
+}
+
+impl From<HNSW> for AnyIndex {
+    fn from(hnsw: HNSW) -> Self {
+        AnyIndex::Hnsw(hnsw)
+    }
+}
+
+impl From<FlatIndex> for AnyIndex {
+    fn from(flat: FlatIndex) -> Self {
+        AnyIndex::Flat(flat)
+    }
+}
+
+impl From<IvfIndex> for AnyIndex {
+    fn from(ivf: IvfIndex) -> Self {
+        AnyIndex::Ivf(ivf)
+    }
+}
+
+impl Index for AnyIndex {
+    fn kind(&self) -> IndexKind {
+        self.inner().kind()
+    }
+
+    fn dim(&self) -> usize {
+        self.inner().dim()
+    }
+
+    fn len(&self) -> usize {
+        self.inner().len()
+    }
+
+    fn insert(&mut self, vec: &[f32]) -> usize {
+        self.inner_mut().insert(vec)
+    }
+
+    fn delete(&mut self, id: usize) -> bool {
+        self.inner_mut().delete(id)
+    }
+
+    fn get_vector(&self, id: usize) -> Option<&[f32]> {
+        self.inner().get_vector(id)
+    }
+
+    fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        self.inner().search(query, k)
+    }
+
+    fn search_batch(&self, queries: &[f32], k: usize) -> Vec<Vec<(f32, usize)>> {
+        self.inner().search_batch(queries, k)
+    }
+
+    // with the header, so load knows which index it is
+    fn save(&self, path: &Path) -> Result<(), String> {
+        write(path, &self.to_bytes()?)
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        AnyIndex::from_bytes(&read(path)?)
+    }
+}
@@ -0,0 +1,251 @@
+// Document ingestion: walk a directory of .txt / .md files, split them into chunks and
+// push the chunks + caller supplied embeddings into a PhotonDB.
+// Every chunk remembers where it came from (path + byte range into the file), which ends up
+// in the payload as "source", "start", "end" (+ "heading" for markdown) next to "text".
+
+use crate::persistence::PhotonDB;
+use crate::{Payload, PayloadValue};
+use std::cmp::min;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub source: PathBuf,
+    // byte offsets into the source file, text == file[start..end]
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+    // "Intro > Setup" style path of the enclosing markdown headings
+    pub heading: Option<String>,
+}
+
+impl Chunk {
+    pub fn payload(&self) -> Payload {
+        let mut payload = Payload::new();
+        payload.insert("text".to_string(), PayloadValue::from(self.text.as_str()));
+        payload.insert("source".to_string(), PayloadValue::from(self.source.to_string_lossy().to_string()));
+        payload.insert("start".to_string(), PayloadValue::Int(self.start as i64));
+        payload.insert("end".to_string(), PayloadValue::Int(self.end as i64));
+        if let Some(heading) = &self.heading {
+            payload.insert("heading".to_string(), PayloadValue::from(heading.as_str()));
+        }
+        payload
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Chunker {
+    // blank line separated blocks, shorter ones (in bytes) are dropped like benchmark.py did
+    Paragraph { min_len: usize },
+    // `size` whitespace separated tokens per chunk, consecutive chunks share `overlap` tokens
+    TokenWindow { size: usize, overlap: usize },
+    // one chunk per heading section, sections longer than `max_len` bytes get paragraph split
+    Markdown { max_len: usize },
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Chunker::Paragraph { min_len: 20 }
+    }
+}
+
+// (start, end) of the trimmed text in s[start..end], None if it is all whitespace
+fn trim_span(s: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    let piece = &s[start..end];
+    let trimmed = piece.trim_start();
+    let start = start + (piece.len() - trimmed.len());
+    let end = start + trimmed.trim_end().len();
+    (start < end).then_some((start, end))
+}
+
+// byte spans of blank line separated blocks inside text[from..to]
+fn paragraph_spans(text: &str, from: usize, to: usize) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut block_start = from;
+    let mut pos = from;
+    for line in text[from..to].split_inclusive('\n') {
+        if line.trim().is_empty() {
+            spans.extend(trim_span(text, block_start, pos));
+            block_start = pos + line.len();
+        }
+        pos += line.len();
+    }
+    spans.extend(trim_span(text, block_start, to));
+    spans
+}
+
+fn token_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+impl Chunker {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Chunker::TokenWindow { size, overlap } if size == 0 || overlap >= size => {
+                Err(format!("Token window needs size > overlap, got size {} overlap {}", size, overlap))
+            }
+            Chunker::Markdown { max_len: 0 } => Err("Markdown max_len must be > 0".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    // Splits one document, offsets are relative to `text`
+    pub fn split(&self, source: &Path, text: &str) -> Result<Vec<Chunk>, String> {
+        self.validate()?;
+        let chunk = |(start, end): (usize, usize), heading: Option<String>| Chunk {
+            source: source.to_path_buf(),
+            start,
+            end,
+            text: text[start..end].to_string(),
+            heading,
+        };
+
+        Ok(match *self {
+            Chunker::Paragraph { min_len } => paragraph_spans(text, 0, text.len())
+                .into_iter()
+                .filter(|(s, e)| e - s >= min_len)
+                .map(|span| chunk(span, None))
+                .collect(),
+            Chunker::TokenWindow { size, overlap } => {
+                let tokens = token_spans(text);
+                let step = size - overlap;
+                let mut chunks = Vec::new();
+                let mut i = 0;
+                while i < tokens.len() {
+                    let last = min(i + size, tokens.len()) - 1;
+                    chunks.push(chunk((tokens[i].0, tokens[last].1), None));
+                    if last == tokens.len() - 1 {
+                        break;
+                    }
+                    i += step;
+                }
+                chunks
+            }
+            Chunker::Markdown { max_len } => {
+                // section = heading line up to the next heading, headings inside ``` fences don't count
+                let mut sections: Vec<(usize, Option<String>)> = vec![(0, None)];
+                let mut stack: Vec<(usize, String)> = Vec::new();
+                let mut in_fence = false;
+                let mut pos = 0;
+                for line in text.split_inclusive('\n') {
+                    let trimmed = line.trim_start();
+                    if trimmed.starts_with("```") {
+                        in_fence = !in_fence;
+                    }
+                    let level = trimmed.chars().take_while(|&c| c == '#').count();
+                    let is_heading =
+                        !in_fence && (1..=6).contains(&level) && trimmed[level..].starts_with([' ', '\t', '\n']);
+                    if is_heading {
+                        stack.retain(|(l, _)| *l < level);
+                        stack.push((level, trimmed[level..].trim().to_string()));
+                        let path: Vec<&str> = stack.iter().map(|(_, t)| t.as_str()).collect();
+                        sections.push((pos, Some(path.join(" > "))));
+                    }
+                    pos += line.len();
+                }
+
+                let mut chunks = Vec::new();
+                for (i, (start, heading)) in sections.iter().enumerate() {
+                    let end = sections.get(i + 1).map_or(text.len(), |s| s.0);
+                    let Some(span) = trim_span(text, *start, end) else {
+                        continue;
+                    };
+                    if span.1 - span.0 <= max_len {
+                        chunks.push(chunk(span, heading.clone()));
+                    } else {
+                        for span in paragraph_spans(text, span.0, span.1) {
+                            chunks.push(chunk(span, heading.clone()));
+                        }
+                    }
+                }
+                chunks
+            }
+        })
+    }
+}
+
+// .txt and .md files under dir, recursively, sorted so ingestion order is stable.
+// Symlinked directories aren't followed, a link back up the tree would never end.
+pub fn walk(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let path = entry.path();
+            let file_type = entry.file_type().map_err(|e| format!("{}: {}", path.display(), e))?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if matches!(path.extension().and_then(|e| e.to_str()), Some("txt" | "md")) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+pub fn chunk_file(path: &Path, chunker: &Chunker) -> Result<Vec<Chunk>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    chunker.split(path, &text)
+}
+
+pub fn chunk_dir(dir: &Path, chunker: &Chunker) -> Result<Vec<Chunk>, String> {
+    let mut chunks = Vec::new();
+    for path in walk(dir)? {
+        chunks.extend(chunk_file(&path, chunker)?);
+    }
+    Ok(chunks)
+}
+
+impl PhotonDB {
+    // embeddings[i] belongs to chunks[i], returns the new ids in the same order
+    pub fn ingest(&mut self, chunks: &[Chunk], embeddings: &[Vec<f32>]) -> Result<Vec<usize>, String> {
+        if chunks.len() != embeddings.len() {
+            return Err(format!("Got {} chunks but {} embeddings", chunks.len(), embeddings.len()));
+        }
+        if let Some(e) = embeddings.iter().find(|e| e.len() != self.dim) {
+            return Err(format!("Vector dimension mismatch: expected {}, got {}", self.dim, e.len()));
+        }
+        chunks
+            .iter()
+            .zip(embeddings)
+            .map(|(chunk, embedding)| self.add_with_payload(embedding, chunk.payload()))
+            .collect()
+    }
+
+    // Walks, chunks and embeds file by file, `embed` gets the chunk texts of one file at a time
+    pub fn ingest_dir<F>(&mut self, dir: &Path, chunker: &Chunker, mut embed: F) -> Result<Vec<usize>, String>
+    where
+        F: FnMut(&[&str]) -> Result<Vec<Vec<f32>>, String>,
+    {
+        let mut ids = Vec::new();
+        for path in walk(dir)? {
+            let chunks = chunk_file(&path, chunker)?;
+            if chunks.is_empty() {
+                continue;
+            }
+            let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+            let embeddings = embed(&texts)?;
+            ids.extend(self.ingest(&chunks, &embeddings)?);
+        }
+        Ok(ids)
+    }
+}
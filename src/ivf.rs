@@ -0,0 +1,540 @@
+// IVF: k-means centroids split the space into nlist cells, every vector lives in the
+// inverted list of its nearest centroid, a query only scans the nprobe closest lists.
+// Training is one batch k-means pass over the stored vectors, much cheaper than building
+// a graph, so it suits indexes that get rebuilt from scratch.
+//
+// Until train() runs everything sits in a single list and search is exact.
+// With PQ on, each list also keeps m byte codes per vector. Lists are scanned with the
+// codes and the best RERANK * k get re-scored on the full vectors.
+
+use crate::{Metric, VectorStore};
+use ordered_float::OrderedFloat;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// k-means never needs more than this many points per centroid to settle
+const MAX_POINTS_PER_CENTROID: usize = 256;
+const RERANK: usize = 4;
+// codes are one byte per sub-vector
+const PQ_CENTROIDS: usize = 256;
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct IvfConfig {
+    pub dim: usize,
+    pub metric: Metric,
+    // number of k-means cells
+    pub nlist: usize,
+    // default lists scanned per query, can be overridden per call
+    pub nprobe: usize,
+    // PQ sub-vectors per vector, 0 = no codes. dim must be a multiple of it
+    pub pq_m: usize,
+    // k-means rounds
+    pub iterations: usize,
+    pub seed: Option<u64>,
+}
+
+impl IvfConfig {
+    pub fn new(dim: usize) -> Self {
+        IvfConfig {
+            dim,
+            metric: Metric::Euclidean,
+            nlist: 100,
+            nprobe: 8,
+            pq_m: 0,
+            iterations: 20,
+            seed: None,
+        }
+    }
+
+    pub fn metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn nlist(mut self, nlist: usize) -> Self {
+        self.nlist = nlist;
+        self
+    }
+
+    pub fn nprobe(mut self, nprobe: usize) -> Self {
+        self.nprobe = nprobe;
+        self
+    }
+
+    pub fn pq(mut self, pq_m: usize) -> Self {
+        self.pq_m = pq_m;
+        self
+    }
+
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.dim == 0 {
+            return Err("dim must be greater than 0".to_string());
+        }
+        if self.nlist == 0 {
+            return Err("nlist must be greater than 0".to_string());
+        }
+        if self.nprobe == 0 {
+            return Err("nprobe must be greater than 0".to_string());
+        }
+        if self.iterations == 0 {
+            return Err("iterations must be greater than 0".to_string());
+        }
+        if self.pq_m > 0 && !self.dim.is_multiple_of(self.pq_m) {
+            return Err(format!("dim ({}) must be a multiple of pq m ({})", self.dim, self.pq_m));
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<IvfIndex, String> {
+        self.validate()?;
+        Ok(IvfIndex {
+            vectors: VectorStore::with_metric(0, self.dim, self.metric),
+            config: self,
+            ids: Vec::new(),
+            rows: BTreeMap::new(),
+            next_id: 0,
+            centroids: Vec::new(),
+            lists: vec![IvfList::default()],
+            assignment: Vec::new(),
+            pq: None,
+        })
+    }
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct IvfList {
+    pub rows: Vec<usize>,
+    // pq.m bytes per entry of rows, same order. Empty without PQ
+    pub codes: Vec<u8>,
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ProductQuantizer {
+    // sub-vectors per vector, each dsub floats
+    pub m: usize,
+    pub dsub: usize,
+    // centroids per sub-space, 256 unless trained on fewer points
+    pub ksub: usize,
+    // m * ksub * dsub
+    pub centroids: Vec<f32>,
+    // squared norm of every sub-centroid, m * ksub
+    pub norms: Vec<f32>,
+}
+
+impl ProductQuantizer {
+    pub(crate) fn train(data: &[f32], dim: usize, m: usize, iterations: usize, rng: &mut StdRng) -> Self {
+        let dsub = dim / m;
+        let n = data.len() / dim;
+        let ksub = PQ_CENTROIDS.min(n);
+        let mut centroids = Vec::with_capacity(m * ksub * dsub);
+        for j in 0..m {
+            let sub: Vec<f32> = data
+                .chunks_exact(dim)
+                .flat_map(|v| v[j * dsub..(j + 1) * dsub].iter().copied())
+                .collect();
+            centroids.extend(kmeans(&sub, dsub, ksub, iterations, Metric::Euclidean, rng));
+        }
+        let norms = centroids.chunks_exact(dsub).map(|c| c.iter().map(|x| x * x).sum()).collect();
+        ProductQuantizer { m, dsub, ksub, centroids, norms }
+    }
+
+    // Shape checks for a codebook read from a file, codes are checked by their owner
+    pub(crate) fn validate(&self, dim: usize) -> Result<(), String> {
+        if self.m == 0 || self.m * self.dsub != dim {
+            return Err(format!("PQ has {} sub-vectors of {} floats, dim is {}", self.m, self.dsub, dim));
+        }
+        if self.ksub == 0 || self.ksub > PQ_CENTROIDS {
+            return Err(format!("PQ has {} centroids per sub-space, expected 1..={}", self.ksub, PQ_CENTROIDS));
+        }
+        if self.centroids.len() != self.m * self.ksub * self.dsub || self.norms.len() != self.m * self.ksub {
+            return Err("PQ codebook size doesn't match m * ksub * dsub".to_string());
+        }
+        Ok(())
+    }
+
+    fn centroid(&self, j: usize, c: usize) -> &[f32] {
+        let start = (j * self.ksub + c) * self.dsub;
+        &self.centroids[start..start + self.dsub]
+    }
+
+    pub(crate) fn encode(&self, vec: &[f32], out: &mut Vec<u8>) {
+        for (j, sub) in vec.chunks_exact(self.dsub).enumerate() {
+            let code = (0..self.ksub)
+                .min_by_key(|&c| OrderedFloat(Metric::Euclidean.distance(sub, self.centroid(j, c))))
+                .unwrap();
+            out.push(code as u8);
+        }
+    }
+
+    // dot(query sub-vector, sub-centroid) for every (j, c), computed once per query
+    pub(crate) fn table(&self, query: &[f32]) -> Vec<f32> {
+        let mut table = Vec::with_capacity(self.m * self.ksub);
+        for (j, sub) in query.chunks_exact(self.dsub).enumerate() {
+            table.extend((0..self.ksub).map(|c| sub.iter().zip(self.centroid(j, c)).map(|(a, b)| a * b).sum::<f32>()));
+        }
+        table
+    }
+
+    // Distance between the query and the decoded vector, same scale as Metric::distance
+    pub(crate) fn distance(&self, metric: Metric, table: &[f32], query_norm: f32, code: &[u8]) -> f32 {
+        let (mut dot, mut norm) = (0.0f32, 0.0f32);
+        for (j, &c) in code.iter().enumerate() {
+            let i = j * self.ksub + c as usize;
+            dot += table[i];
+            norm += self.norms[i];
+        }
+        match metric {
+            Metric::Euclidean => query_norm - 2.0 * dot + norm,
+            Metric::InnerProduct => -dot,
+            Metric::Cosine => {
+                if query_norm == 0.0 || norm == 0.0 {
+                    return 1.0;
+                }
+                1.0 - dot / (query_norm.sqrt() * norm.sqrt())
+            }
+        }
+    }
+}
+
+// Cells are cosine cells for cosine, L2 cells otherwise. With inner product a "nearest"
+// centroid by -dot is just the longest one, so everything would land in one list.
+fn coarse_metric(metric: Metric) -> Metric {
+    match metric {
+        Metric::Cosine => Metric::Cosine,
+        Metric::Euclidean | Metric::InnerProduct => Metric::Euclidean,
+    }
+}
+
+fn nearest(centroids: &[f32], dim: usize, vec: &[f32], metric: Metric) -> usize {
+    centroids
+        .chunks_exact(dim)
+        .enumerate()
+        .min_by_key(|(_, c)| OrderedFloat(metric.distance(vec, c)))
+        .map(|(i, _)| i)
+        .unwrap()
+}
+
+// Lloyd's k-means, seeded with k distinct random points. data is n * dim, n >= k
+fn kmeans(data: &[f32], dim: usize, k: usize, iterations: usize, metric: Metric, rng: &mut StdRng) -> Vec<f32> {
+    let n = data.len() / dim;
+    let mut centroids: Vec<f32> = rand::seq::index::sample(rng, n, k)
+        .into_iter()
+        .flat_map(|i| data[i * dim..(i + 1) * dim].iter().copied())
+        .collect();
+    for _ in 0..iterations {
+        let assignment: Vec<usize> = data
+            .par_chunks_exact(dim)
+            .map(|v| nearest(&centroids, dim, v, metric))
+            .collect();
+        let mut sums = vec![0.0f32; k * dim];
+        let mut counts = vec![0usize; k];
+        for (v, &c) in data.chunks_exact(dim).zip(&assignment) {
+            counts[c] += 1;
+            for (s, x) in sums[c * dim..(c + 1) * dim].iter_mut().zip(v) {
+                *s += x;
+            }
+        }
+        for c in 0..k {
+            let centroid = &mut centroids[c * dim..(c + 1) * dim];
+            if counts[c] == 0 {
+                // empty cell, restart it on a random point
+                let i = rng.random_range(0..n);
+                centroid.copy_from_slice(&data[i * dim..(i + 1) * dim]);
+            } else {
+                for (x, s) in centroid.iter_mut().zip(&sums[c * dim..(c + 1) * dim]) {
+                    *x = s / counts[c] as f32;
+                }
+            }
+        }
+    }
+    centroids
+}
+
+// Keeps the k smallest, closest first
+fn top_k(mut results: Vec<(OrderedFloat<f32>, usize)>, k: usize) -> Vec<(OrderedFloat<f32>, usize)> {
+    if k < results.len() {
+        results.select_nth_unstable(k);
+        results.truncate(k);
+    }
+    results.sort_unstable();
+    results
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+pub struct IvfIndex {
+    pub config: IvfConfig,
+    pub vectors: VectorStore,
+    pub ids: Vec<usize>,              // row -> external id
+    pub rows: BTreeMap<usize, usize>, // external id -> row
+    pub next_id: usize,
+    // nlist * dim after training, empty before
+    pub centroids: Vec<f32>,
+    pub lists: Vec<IvfList>,
+    // row -> list
+    pub assignment: Vec<usize>,
+    pub pq: Option<ProductQuantizer>,
+}
+
+impl IvfIndex {
+    pub fn new(dim: usize, metric: Metric, nlist: usize) -> Result<Self, String> {
+        IvfConfig::new(dim).metric(metric).nlist(nlist).build()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn is_trained(&self) -> bool {
+        !self.centroids.is_empty()
+    }
+
+    fn code_len(&self) -> usize {
+        self.pq.as_ref().map_or(0, |pq| pq.m)
+    }
+
+    fn rng(&self) -> StdRng {
+        match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        }
+    }
+
+    fn assign(&self, vec: &[f32]) -> usize {
+        if !self.is_trained() {
+            return 0;
+        }
+        nearest(&self.centroids, self.config.dim, vec, coarse_metric(self.config.metric))
+    }
+
+    fn push_to_list(&mut self, list: usize, row: usize) {
+        if let Some(pq) = &self.pq {
+            pq.encode(self.vectors.get(row), &mut self.lists[list].codes);
+        }
+        self.lists[list].rows.push(row);
+    }
+
+    pub fn insert(&mut self, vec: &[f32]) -> usize {
+        assert_eq!(vec.len(), self.config.dim, "Vector dimension mismatch");
+        let id = self.next_id;
+        self.next_id += 1;
+        let list = self.assign(vec);
+        let row = self.vectors.insert(vec);
+        self.ids.push(id);
+        self.rows.insert(id, row);
+        self.assignment.push(list);
+        self.push_to_list(list, row);
+        id
+    }
+
+    // Swap-remove like FlatIndex, the last row takes over the hole in its list too
+    pub fn delete(&mut self, id: usize) -> bool {
+        let Some(row) = self.rows.remove(&id) else {
+            return false;
+        };
+        let code_len = self.code_len();
+        let list = &mut self.lists[self.assignment[row]];
+        let pos = list.rows.iter().position(|&r| r == row).unwrap();
+        list.rows.swap_remove(pos);
+        if code_len > 0 {
+            let last_code = list.codes.len() - code_len;
+            list.codes.copy_within(last_code.., pos * code_len);
+            list.codes.truncate(last_code);
+        }
+
+        let last = self.ids.len() - 1;
+        self.vectors.swap_remove(row);
+        if row != last {
+            self.ids[row] = self.ids[last];
+            self.rows.insert(self.ids[row], row);
+            self.assignment[row] = self.assignment[last];
+            let moved = &mut self.lists[self.assignment[row]].rows;
+            let pos = moved.iter().position(|&r| r == last).unwrap();
+            moved[pos] = row;
+        }
+        self.ids.pop();
+        self.assignment.pop();
+        true
+    }
+
+    pub fn get_vector(&self, id: usize) -> Option<&[f32]> {
+        self.rows.get(&id).map(|&row| self.vectors.get(row))
+    }
+
+    // k-means over (a sample of) the stored vectors, then every vector is reassigned and
+    // PQ codes are rebuilt. Can be called again after a lot of inserts to refresh the cells.
+    pub fn train(&mut self) -> Result<(), String> {
+        let (dim, nlist) = (self.config.dim, self.config.nlist);
+        if self.len() < nlist {
+            return Err(format!("Need at least nlist ({}) vectors to train, got {}", nlist, self.len()));
+        }
+        let mut rng = self.rng();
+        let sample_size = self.len().min(nlist * MAX_POINTS_PER_CENTROID);
+        let sample: Vec<f32> = rand::seq::index::sample(&mut rng, self.len(), sample_size)
+            .into_iter()
+            .flat_map(|row| self.vectors.get(row).iter().copied())
+            .collect();
+
+        let metric = coarse_metric(self.config.metric);
+        self.centroids = kmeans(&sample, dim, nlist, self.config.iterations, metric, &mut rng);
+        self.pq = match self.config.pq_m {
+            0 => None,
+            m => Some(ProductQuantizer::train(&sample, dim, m, self.config.iterations, &mut rng)),
+        };
+
+        self.assignment = (0..self.len())
+            .into_par_iter()
+            .map(|row| nearest(&self.centroids, dim, self.vectors.get(row), metric))
+            .collect();
+        self.lists = vec![IvfList::default(); nlist];
+        for row in 0..self.len() {
+            self.push_to_list(self.assignment[row], row);
+        }
+        Ok(())
+    }
+
+    // (distance, id) closest first. The nprobe closest lists are scanned in parallel,
+    // nprobe defaults to the one in the config.
+    pub fn search_with_nprobe(&self, query: &[f32], k: usize, nprobe: Option<usize>) -> Vec<(f32, usize)> {
+        assert_eq!(query.len(), self.config.dim, "Query dimension mismatch");
+        let nprobe = nprobe.unwrap_or(self.config.nprobe).max(1);
+        let probes: Vec<usize> = if self.is_trained() {
+            let metric = coarse_metric(self.config.metric);
+            let cells = self
+                .centroids
+                .chunks_exact(self.config.dim)
+                .enumerate()
+                .map(|(i, c)| (OrderedFloat(metric.distance(query, c)), i))
+                .collect();
+            top_k(cells, nprobe).into_iter().map(|(_, i)| i).collect()
+        } else {
+            vec![0]
+        };
+
+        let metric = self.config.metric;
+        let hits = match &self.pq {
+            None => {
+                let hits = probes
+                    .par_iter()
+                    .flat_map_iter(|&l| {
+                        let scored = self.lists[l]
+                            .rows
+                            .iter()
+                            .map(|&row| (OrderedFloat(self.vectors.distance_to_query(row, query)), row))
+                            .collect();
+                        top_k(scored, k)
+                    })
+                    .collect();
+                top_k(hits, k)
+            }
+            Some(pq) => {
+                let table = pq.table(query);
+                let query_norm = query.iter().map(|x| x * x).sum();
+                let depth = k * RERANK;
+                let candidates = probes
+                    .par_iter()
+                    .flat_map_iter(|&l| {
+                        let list = &self.lists[l];
+                        let scored = list
+                            .rows
+                            .iter()
+                            .zip(list.codes.chunks_exact(pq.m))
+                            .map(|(&row, code)| (OrderedFloat(pq.distance(metric, &table, query_norm, code)), row))
+                            .collect();
+                        top_k(scored, depth)
+                    })
+                    .collect();
+                let rescored = top_k(candidates, depth)
+                    .into_iter()
+                    .map(|(_, row)| (OrderedFloat(self.vectors.distance_to_query(row, query)), row))
+                    .collect();
+                top_k(rescored, k)
+            }
+        };
+        hits.into_iter().map(|(OrderedFloat(d), row)| (d, self.ids[row])).collect()
+    }
+
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        self.search_with_nprobe(query, k, None)
+    }
+
+    // Every row scored on the full vector, ignores the lists
+    pub fn brute_force_search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        let scored = (0..self.len())
+            .into_par_iter()
+            .map(|row| (OrderedFloat(self.vectors.distance_to_query(row, query)), row))
+            .collect();
+        top_k(scored, k).into_iter().map(|(OrderedFloat(d), row)| (d, self.ids[row])).collect()
+    }
+
+    // Consistency check for loaded files, so insert / delete / search can't index out of
+    // bounds: every row in exactly one list, the one it's assigned to
+    pub fn validate(&self) -> Result<(), String> {
+        self.config.validate()?;
+        let dim = self.config.dim;
+        if self.vectors.dim != dim {
+            return Err(format!("IVF vectors are {}-d, config has dim {}", self.vectors.dim, dim));
+        }
+        if self.vectors.is_tiered() {
+            return Err("An IVF index deletes in place, its vectors can't be tiered".to_string());
+        }
+        let n = self.ids.len();
+        if self.vectors.len() != n || self.rows.len() != n || self.assignment.len() != n {
+            return Err("IVF index row count mismatch".to_string());
+        }
+        if self.rows.iter().any(|(&id, &row)| self.ids.get(row) != Some(&id)) {
+            return Err("IVF id -> row map doesn't match the ids".to_string());
+        }
+        let expected_lists = if self.is_trained() { self.centroids.len() / dim } else { 1 };
+        if self.lists.len() != expected_lists || (self.is_trained() && self.centroids.len() != self.lists.len() * dim) {
+            return Err(format!("IVF has {} lists for {} centroid floats", self.lists.len(), self.centroids.len()));
+        }
+        if let Some(&list) = self.assignment.iter().find(|&&l| l >= self.lists.len()) {
+            return Err(format!("IVF assignment points at list {}, there are {}", list, self.lists.len()));
+        }
+        if let Some(pq) = &self.pq {
+            pq.validate(dim)?;
+        }
+
+        let code_len = self.code_len();
+        let mut listed = vec![false; n];
+        for (l, list) in self.lists.iter().enumerate() {
+            if list.codes.len() != list.rows.len() * code_len {
+                return Err(format!("IVF list {} has a bad code length", l));
+            }
+            if let Some(pq) = &self.pq {
+                if list.codes.iter().any(|&c| c as usize >= pq.ksub) {
+                    return Err(format!("IVF list {} has PQ codes past the codebook", l));
+                }
+            }
+            for &row in &list.rows {
+                if row >= n || self.assignment[row] != l {
+                    return Err(format!("IVF list {} holds a row assigned elsewhere", l));
+                }
+                if std::mem::replace(&mut listed[row], true) {
+                    return Err(format!("IVF row {} is listed twice", row));
+                }
+            }
+        }
+        if let Some(row) = listed.iter().position(|&seen| !seen) {
+            return Err(format!("IVF row {} is in no list", row));
+        }
+        Ok(())
+    }
+}
@@ -1,44 +1,135 @@
+// pyo3 0.20 #[pymethods] expands into non-local impls
+#![allow(non_local_definitions)]
 use ordered_float::OrderedFloat;
 use rayon::prelude::*;
  use std::fmt::Debug;
  use std::fmt::Formatter;
 // use serde::{Serialize, Deserialize};
-use rkyv::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::cmp::min;
-use rand::Rng;
 use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::time::Instant;
 // Expreimenting 
 use rkyv::{Deserialize, rancor::Error, Archive, Serialize};
 // use rkyv::Archive;
 
+pub mod collection;
+pub mod config;
+pub mod context;
+pub mod diagnostics;
+pub mod disk;
+pub mod embed;
+pub mod export;
+pub mod flat;
+pub mod group;
+pub mod index;
+pub mod ingest;
+pub mod ivf;
+pub mod maintenance;
+pub mod mmr;
+pub mod multi;
+pub mod payload;
 pub mod persistence;
+pub mod recommend;
+pub mod reorder;
+pub mod sparse;
+pub mod stats;
+pub mod text;
+pub mod tiered;
+pub mod vamana;
 pub mod wrapper;
 
+pub use collection::Collection;
+pub use config::{HnswConfig, Metric};
+pub use context::{Citation, RetrievedContext, TokenCounter, WhitespaceTokens};
+pub use diagnostics::{GraphDiagnostics, LayerDiagnostics};
+pub use disk::{DiskIndex, ReadMode};
+pub use embed::{Embedder, HashingEmbedder, WordVectors};
+pub use flat::FlatIndex;
+pub use group::Group;
+pub use index::{AnyIndex, Index, IndexKind};
+pub use ingest::{Chunk, Chunker};
+pub use ivf::{IvfConfig, IvfIndex};
+pub use maintenance::RepairReport;
+pub use multi::MultiVectorIndex;
+pub use payload::{Payload, PayloadValue};
+pub use recommend::RecommendStrategy;
+pub use reorder::ReorderStrategy;
+pub use sparse::{SparseIndex, SparseVector};
+pub use stats::{LayerStats, SearchStats};
+pub use text::{Fusion, TextIndex};
+pub use tiered::TierConfig;
+pub use vamana::{VamanaConfig, VamanaIndex};
+
 
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 pub struct VectorStore {
+    // every row, or with a tier only the ones after mapped_rows
     pub data: Vec<f32>,
     pub dim: usize,
+    pub metric: Metric,
+    // Some = rows live in a mapped file, see tiered.rs
+    pub tier: Option<TierConfig>,
+    pub mapped_rows: usize,
+    #[rkyv(with = rkyv::with::Skip)]
+    pub mapping: tiered::Mapping,
 }
 
 impl VectorStore {
     pub fn new(n: usize, dim: usize) -> Self {
+        Self::with_metric(n, dim, Metric::Euclidean)
+    }
+
+    pub fn with_metric(n: usize, dim: usize, metric: Metric) -> Self {
         Self {
             data: Vec::with_capacity(n * dim),
             dim,
+            metric,
+            tier: None,
+            mapped_rows: 0,
+            mapping: tiered::Mapping::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mapped_rows + self.data.len() / self.dim
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, id: usize) -> &[f32] {
+        if id < self.mapped_rows {
+            return self.mapped_row(id);
         }
+        let id = id - self.mapped_rows;
+        &self.data[id * self.dim..(id + 1) * self.dim]
     }
     
+    // Always into RAM, a tiered store moves the tail to its file in enforce_budget()
     pub fn insert(&mut self, vec: &[f32]) -> usize {
-        let id = self.data.len() / self.dim;
+        let id = self.len();
         self.data.extend_from_slice(vec);
         id
     }
+
+    // The last row moves into `row`, then gets dropped. Mapped rows are read only, so
+    // indexes that delete in place (flat, ivf) can't be tiered.
+    pub(crate) fn swap_remove(&mut self, row: usize) {
+        assert!(!self.is_tiered(), "Rows of a tiered store can't be removed in place");
+        let (dim, last) = (self.dim, self.len() - 1);
+        if row != last {
+            self.data.copy_within(last * dim..(last + 1) * dim, row * dim);
+        }
+        self.data.truncate(last * dim);
+    }
     
     pub fn squared_distance(&self, v1_id: usize, v2_id: usize) -> f32 {
-        let vec1 = &self.data[v1_id * self.dim..(v1_id + 1) * self.dim];
-        let vec2 = &self.data[v2_id * self.dim..(v2_id + 1) * self.dim];
+        let vec1 = self.get(v1_id);
+        let vec2 = self.get(v2_id);
         
         let mut sum = 0.0;
         let chunks1 = vec1.chunks_exact(8);
@@ -63,7 +154,7 @@ impl VectorStore {
     }
     
     pub fn squared_distance_to_query(&self, v1_id: usize, query: &[f32]) -> f32 {
-        let vec1 = &self.data[v1_id * self.dim..(v1_id + 1) * self.dim];
+        let vec1 = self.get(v1_id);
         
         let mut sum = 0.0;
         let chunks1 = vec1.chunks_exact(8);
@@ -86,13 +177,29 @@ impl VectorStore {
         }
         sum
     }
+
+    // Distance under the store's metric, smaller is always closer
+    pub fn distance(&self, v1_id: usize, v2_id: usize) -> f32 {
+        match self.metric {
+            Metric::Euclidean => self.squared_distance(v1_id, v2_id),
+            _ => self.metric.distance(self.get(v1_id), self.get(v2_id)),
+        }
+    }
+
+    pub fn distance_to_query(&self, v1_id: usize, query: &[f32]) -> f32 {
+        match self.metric {
+            Metric::Euclidean => self.squared_distance_to_query(v1_id, query),
+            _ => self.metric.distance(self.get(v1_id), query),
+        }
+    }
 }
 
 
 #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 pub struct GraphLayers {
     pub base_layer: Vec<Vec<usize>>,
-    pub upper_layers: Vec<HashMap<usize, Vec<usize>>>,
+    // BTreeMap keeps the serialized bytes stable between identical builds
+    pub upper_layers: Vec<BTreeMap<usize, Vec<usize>>>,
 }
 
 impl std::fmt::Debug for ArchivedGraphLayers{
@@ -100,8 +207,6 @@ impl std::fmt::Debug for ArchivedGraphLayers{
 }
 // #[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
 
-struct Distibution {}
-
 impl GraphLayers {
     fn initialize_node(&mut self, node_id: usize, target_level: usize) {
         self.base_layer.push(Vec::new());
@@ -110,17 +215,17 @@ impl GraphLayers {
         // and index from 0 so zero is writen here
         for l in 0..target_level {
             if l >= self.upper_layers.len() {
-                self.upper_layers.push(HashMap::new());
+                self.upper_layers.push(BTreeMap::new());
             }
             self.upper_layers[l].insert(node_id, Vec::new());
         }
     }
-    fn add_neighbors(&mut self) {
-        // TO-DO
-    }
-
-    fn shrink_edge() {
-        // TODO
+    // number of upper layers the node lives in (0 = base layer only)
+    pub fn node_level(&self, node_id: usize) -> usize {
+        self.upper_layers
+            .iter()
+            .take_while(|layer| layer.contains_key(&node_id))
+            .count()
     }
 
     fn add_edge(&mut self, node_id_1: usize, node_id_2: usize, layer: usize, d: bool) {
@@ -145,12 +250,14 @@ impl GraphLayers {
         }
     }
 
+    #[allow(dead_code)]
     fn insert_node(&mut self) -> usize {
         let node_id = self.base_layer.len(); // index + 1 
         self.base_layer.push(Vec::new());
         node_id
     }
 
+    #[allow(dead_code)]
     fn insert_node_upper_layers(&mut self, layer: usize, node_id: usize) {
         self.upper_layers[layer - 1].insert(node_id, Vec::new());
     }
@@ -198,29 +305,172 @@ pub struct HNSW {
     pub vectors: VectorStore,
     pub entry_point: Option<usize>,
     pub max_level: usize,
-    pub ef_construction: usize,
-    pub m: usize,
+    pub config: HnswConfig,
+    // Graph + store work on internal slots, callers only ever see external ids.
+    // Both are equal until compact() squeezes the deleted slots out.
+    pub ids: Vec<usize>,          // slot -> external id
+    pub slots: BTreeMap<usize, usize>, // external id -> slot, live points only
+    pub deleted: Vec<bool>,       // soft delete flag per slot
+    pub next_id: usize,
+    pub payloads: BTreeMap<usize, Payload>, // external id -> metadata
+    pub text_index: TextIndex,              // BM25 over the text payload field
+    pub sparse_index: SparseIndex,          // external id -> sparse vector, inverted lists
 }
 
 impl HNSW {
     pub fn new(max_elements: usize, dim: usize) -> Self {
+        HNSW::from_config(HnswConfig::new(dim).max_elements(max_elements))
+    }
+
+    // Unchecked, go through HnswConfig::build() for validation
+    pub(crate) fn from_config(config: HnswConfig) -> Self {
         let layers = GraphLayers::new(16); // Default max levels
-        let vectors = VectorStore::new(max_elements, dim);
+        let vectors = VectorStore::with_metric(config.max_elements, config.dim, config.metric);
         HNSW {
             layers,
             vectors,
             entry_point: None,
             max_level: 16,
-            ef_construction: 64, // Default
-            m: 16,               // Default
+            config,
+            ids: Vec::new(),
+            slots: BTreeMap::new(),
+            deleted: Vec::new(),
+            next_id: 0,
+            payloads: BTreeMap::new(),
+            text_index: TextIndex::default(),
+            sparse_index: SparseIndex::default(),
         }
     }
 
-    pub fn insert(&mut self, q: usize, M: usize, Mmax: usize, ef_construction: usize, m_l: f32) {
+    // Returns the external id of the new point
+    pub fn insert(&mut self, vec: &[f32]) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.insert_with_id(id, vec);
+        id
+    }
+
+    pub fn insert_with_payload(&mut self, vec: &[f32], payload: Payload) -> usize {
+        let id = self.insert(vec);
+        self.index_text(id, &payload);
+        self.payloads.insert(id, payload);
+        id
+    }
+
+    pub(crate) fn insert_with_id(&mut self, id: usize, vec: &[f32]) -> usize {
+        assert_eq!(vec.len(), self.vectors.dim, "Vector dimension mismatch");
+        let q = self.vectors.insert(vec);
+        self.ids.push(id);
+        self.deleted.push(false);
+        self.slots.insert(id, q);
+        self.link(q);
+        q
+    }
+
+    // Soft delete: the node stays in the graph for routing but never shows up in results.
+    // compact() reclaims the space.
+    pub fn delete(&mut self, id: usize) -> bool {
+        match self.slots.remove(&id) {
+            Some(slot) => {
+                self.deleted[slot] = true;
+                self.unindex_text(id);
+                self.payloads.remove(&id);
+                self.sparse_index.remove(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Supersedes the stored vector: the old slot is soft deleted and the id points at a new one.
+    // The payload stays with the id.
+    pub fn update(&mut self, id: usize, vec: &[f32]) -> bool {
+        let slot = match self.slots.remove(&id) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        self.deleted[slot] = true;
+        self.insert_with_id(id, vec);
+        true
+    }
+
+    pub fn get_vector(&self, id: usize) -> Option<&[f32]> {
+        self.slot_of(id).map(|slot| self.vectors.get(slot))
+    }
+
+    pub fn get_vectors(&self, ids: &[usize]) -> Vec<Option<&[f32]>> {
+        ids.iter().map(|&id| self.get_vector(id)).collect()
+    }
+
+    pub fn payload(&self, id: usize) -> Option<&Payload> {
+        self.payloads.get(&id)
+    }
+
+    pub fn set_payload(&mut self, id: usize, payload: Payload) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        self.unindex_text(id);
+        self.index_text(id, &payload);
+        self.payloads.insert(id, payload);
+        true
+    }
+
+    // (id, vector, payload) for every live point, in id order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &[f32], Option<&Payload>)> + '_ {
+        self.slots
+            .iter()
+            .map(move |(&id, &slot)| (id, self.vectors.get(slot), self.payloads.get(&id)))
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.slots.contains_key(&id)
+    }
+
+    // live points
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn deleted_count(&self) -> usize {
+        self.vectors.len() - self.slots.len()
+    }
+
+    pub fn slot_of(&self, id: usize) -> Option<usize> {
+        self.slots.get(&id).copied()
+    }
+
+    // drops deleted slots and translates the rest to external ids
+    fn to_external(&self, results: impl IntoIterator<Item = (f32, usize)>) -> Vec<(f32, usize)> {
+        results
+            .into_iter()
+            .filter(|&(_, slot)| !self.deleted[slot])
+            .map(|(d, slot)| (d, self.ids[slot]))
+            .collect()
+    }
+
+    fn random_level(&self, q: usize) -> usize {
+        let u: f32 = match self.config.seed {
+            // Per-node stream instead of one stored RNG, so a reloaded index
+            // keeps drawing the same levels it would have without the round trip
+            Some(seed) => {
+                let node_seed = seed ^ (q as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                StdRng::seed_from_u64(node_seed).random()
+            }
+            None => rand::random(),
+        };
+        (-(1.0 - u).ln() * self.config.m_l).floor() as usize
+    }
+
+    // Wires an already stored vector into the graph using the persisted config
+    fn link(&mut self, q: usize) {
         let mut w: BinaryHeap<Reverse<(OrderedFloat<f32>, usize)>> = BinaryHeap::new(); // Min Heap to get nearest dist_sq or node_id 
 
-        let u: f32 = rand::random();
-        let level = (-(1.0 - u).ln() * m_l).floor() as usize;
+        let level = self.random_level(q);
         self.layers.initialize_node(q, level);
 
         let mut ep = match self.entry_point {
@@ -232,15 +482,11 @@ impl HNSW {
             }
         };
 
-        let top_level = self.layers.upper_layers.len();
+        let top_level = self.layers.node_level(ep);
+        let query = self.vectors.get(q).to_vec();
 
         for lc in ((level + 1)..=top_level).rev() {
-            let k = self.search_layer(
-                &self.vectors.data[q * self.vectors.dim..(q + 1) * self.vectors.dim],
-                ep,
-                1,
-                lc,
-            );
+            let k = self.search_layer(&query, ep, 1, lc);
             // ep = nearest element in W
             if let Some((OrderedFloat(_), best_node)) = k.peek() {
                 ep = *best_node;
@@ -248,12 +494,7 @@ impl HNSW {
         }
 
         for lc in (0..=min(top_level, level)).rev() {
-            let k = self.search_layer(
-                &self.vectors.data[q * self.vectors.dim..(q + 1) * self.vectors.dim],
-                ep,
-                ef_construction,
-                lc,
-            );
+            let k = self.search_layer(&query, ep, self.config.ef_construction, lc);
             for (OrderedFloat(dist_sq), node_id) in k {
                 w.push(Reverse((OrderedFloat(dist_sq), node_id)));
             }
@@ -266,31 +507,27 @@ impl HNSW {
 
             let candidates = w.clone().into_vec();
 
-            let neighbors = HNSW::select_neighbors_simple(
-                &self.vectors.data[q * self.vectors.dim..(q + 1) * self.vectors.dim],
-                candidates,
-                M,
-                lc,
-            );
+            let neighbors = HNSW::select_neighbors_simple(&query, candidates, self.config.m, lc);
 
             for node in &neighbors {
                 self.layers.add_edge(q, *node, lc, false);
             }
 
+            let m_max = if lc == 0 { self.config.m_max0 } else { self.config.m_max };
             for e in &neighbors {
                 // Shrink connections
-                let eConn = self.layers.get_neighbors(lc, *e);
-                if eConn.len() > Mmax {
+                let e_conn = self.layers.get_neighbors(lc, *e);
+                if e_conn.len() > m_max {
                     // Calculate distances for eConn to create candidates
                     let mut conn_candidates = Vec::new();
-                    for &n in eConn {
-                        let dist = self.vectors.squared_distance(*e, n);
+                    for &n in e_conn {
+                        let dist = self.vectors.distance(*e, n);
                         conn_candidates.push(Reverse((OrderedFloat(dist), n)));
                     }
                     let e_new_conn = HNSW::select_neighbors_simple(
-                        &self.vectors.data[e * self.vectors.dim..(e + 1) * self.vectors.dim],
+                        self.vectors.get(*e),
                         conn_candidates,
-                        Mmax,
+                        m_max,
                         lc,
                     );
                     self.layers.set_neighbors(lc, *e, e_new_conn);
@@ -298,6 +535,11 @@ impl HNSW {
             }
             w.clear(); // Clear w for next layer
         }
+
+        // New top level, this node is now the way in
+        if level > top_level {
+            self.entry_point = Some(q);
+        }
     }
 
     // greedy beam search
@@ -308,96 +550,283 @@ impl HNSW {
         ef_construction: usize,
         lc: usize,
     ) -> BinaryHeap<(OrderedFloat<f32>, usize)> {
+        self.search_layer_traced(q, ep, ef_construction, lc, false).0
+    }
+
+    pub fn search_layer_with_stats(
+        &self,
+        q: &[f32],
+        ep: usize,
+        ef_construction: usize,
+        lc: usize,
+    ) -> (BinaryHeap<(OrderedFloat<f32>, usize)>, LayerStats) {
+        self.search_layer_traced(q, ep, ef_construction, lc, false)
+    }
+
+    // live_only: deleted slots are still walked through but never take a place in the result,
+    // so a query gets ef live hits no matter how many deletes are pending compaction
+    fn search_layer_traced(
+        &self,
+        q: &[f32],
+        ep: usize,
+        ef_construction: usize,
+        lc: usize,
+        live_only: bool,
+    ) -> (BinaryHeap<(OrderedFloat<f32>, usize)>, LayerStats) {
         // let ep = self.entry_point.expect("ENTRY POINT ERROR");
-        let sq_dist = VectorStore::squared_distance_to_query(&self.vectors, ep, q);
+        let sq_dist = self.vectors.distance_to_query(ep, q);
         // Candidates is Min Que
         let mut candidates: BinaryHeap<Reverse<(OrderedFloat<f32>, usize)>> = BinaryHeap::new(); // (Dist , node_id)
         // Found Neighbors is Max Que
         let mut found_neighbours: BinaryHeap<(OrderedFloat<f32>, usize)> = BinaryHeap::new();
         let mut visited: HashSet<usize> = HashSet::new();
+        let mut stats = LayerStats {
+            layer: lc,
+            entry_point: ep,
+            distance_evaluations: 1,
+            max_candidates: 1,
+            ..Default::default()
+        };
+
+        let counts = |node: usize| !live_only || !self.deleted[node];
+        // no result yet (only deleted nodes seen) = nothing to beat
+        let worst = |found: &BinaryHeap<(OrderedFloat<f32>, usize)>| found.peek().map_or(f32::INFINITY, |&(OrderedFloat(d), _)| d);
 
         visited.insert(ep);
         candidates.push(Reverse((OrderedFloat(sq_dist), ep)));
-        found_neighbours.push((OrderedFloat(sq_dist), ep));
+        if counts(ep) {
+            found_neighbours.push((OrderedFloat(sq_dist), ep));
+        }
 
         while !candidates.is_empty() {
             let Reverse((OrderedFloat(dist_c), closest_candidate)) = candidates.pop().unwrap();
 
-            let (OrderedFloat(dist_worst), _furthest_element) = *found_neighbours.peek().unwrap();
-
-            if dist_c > dist_worst && found_neighbours.len() >= ef_construction {
+            if dist_c > worst(&found_neighbours) && found_neighbours.len() >= ef_construction {
                 break;
             }
+            stats.hops += 1;
 
-            for e in GraphLayers::get_neighbors(&self.layers, lc, closest_candidate) {
+            // read-ahead for the node most likely expanded next, while this one gets evaluated
+            if let Some(Reverse((_, next))) = candidates.peek() {
+                let next_neighbors = GraphLayers::get_neighbors(&self.layers, lc, *next);
+                self.vectors.prefetch(next_neighbors.iter().copied().filter(|e| !visited.contains(e)));
+            }
+            let neighbors = GraphLayers::get_neighbors(&self.layers, lc, closest_candidate);
+            for e in neighbors {
                 if !visited.contains(e) {
-                    let dist_e = VectorStore::squared_distance_to_query(&self.vectors, *e, q);
-                    let _dist_e_wrapped = OrderedFloat(dist_e);
+                    let dist_e = self.vectors.distance_to_query(*e, q);
+                    stats.distance_evaluations += 1;
 
-                    let (OrderedFloat(current_worst_dist), _) = *found_neighbours.peek().unwrap();
                     visited.insert(*e);
-                    if dist_e < current_worst_dist || found_neighbours.len() < ef_construction {
+                    if dist_e < worst(&found_neighbours) || found_neighbours.len() < ef_construction {
                         candidates.push(Reverse((OrderedFloat(dist_e), *e)));
-                        found_neighbours.push((OrderedFloat(dist_e), *e));
-                        if found_neighbours.len() > ef_construction {
-                            found_neighbours.pop();
+                        if counts(*e) {
+                            found_neighbours.push((OrderedFloat(dist_e), *e));
+                            if found_neighbours.len() > ef_construction {
+                                found_neighbours.pop();
+                            }
                         }
                     }
                 }
             }
+            stats.max_candidates = stats.max_candidates.max(candidates.len());
         }
-        found_neighbours
+        stats.visited = visited.len();
+        stats.found = found_neighbours.len();
+        (found_neighbours, stats)
         // found_neighbours.into_iter().map(|(_, idx)| idx).collect()
     }
 
     //  K-NN-SEARCH
-    pub fn search(&self, query: &[f32], k: usize, ef_search: usize) -> Vec<(f32, usize)> {
-        let mut ep = match self.entry_point {
+    // ef_search = None uses the default persisted in the config
+    pub fn search(&self, query: &[f32], k: usize, ef_search: Option<usize>) -> Vec<(f32, usize)> {
+        self.search_traced(query, k, ef_search, None)
+    }
+
+    // Same search, plus a trace of the work done on every layer
+    pub fn search_with_stats(&self, query: &[f32], k: usize, ef_search: Option<usize>) -> (Vec<(f32, usize)>, SearchStats) {
+        let start = Instant::now();
+        let mut stats = SearchStats::default();
+        let result = self.search_traced(query, k, ef_search, Some(&mut stats));
+        stats.elapsed = start.elapsed();
+        (result, stats)
+    }
+
+    fn search_traced(
+        &self,
+        query: &[f32],
+        k: usize,
+        ef_search: Option<usize>,
+        stats: Option<&mut SearchStats>,
+    ) -> Vec<(f32, usize)> {
+        let ef_search = ef_search.unwrap_or(self.config.ef_search).max(k);
+        let mut result = self.candidates_traced(query, ef_search, stats);
+        result.truncate(k);
+        result
+    }
+
+    // The whole ef-wide beam from layer 0: live points only, external ids, closest first.
+    // Base for searches that filter or re-rank before cutting down to k.
+    pub(crate) fn candidates(&self, query: &[f32], ef: usize) -> Vec<(f32, usize)> {
+        self.candidates_traced(query, ef, None)
+    }
+
+    fn candidates_traced(&self, query: &[f32], ef: usize, mut stats: Option<&mut SearchStats>) -> Vec<(f32, usize)> {
+        let ep = match self.descend_traced(query, stats.as_deref_mut()) {
             Some(ep) => ep,
             None => return Vec::new(),
         };
 
-        let top_level = self.layers.upper_layers.len();
-        // Phase 1: Greedy search from top to 1
+        let (mut w, layer_stats) = self.search_layer_traced(query, ep, ef, 0, true);
+        if let Some(stats) = stats {
+            stats.push_layer(layer_stats);
+        }
+
+        let mut result = Vec::new();
+        while let Some((OrderedFloat(dist), node_id)) = w.pop() {
+            result.push((dist, node_id));
+        }
+
+        result.reverse();
+        self.to_external(result)
+    }
+
+    // "More like this": neighbors of a stored point, never the point itself or anything in `exclude`.
+    // None if the id doesn't exist.
+    pub fn search_by_id(&self, id: usize, k: usize, ef_search: Option<usize>, exclude: &[usize]) -> Option<Vec<(f32, usize)>> {
+        let query = self.get_vector(id)?;
+        let excluded: HashSet<usize> = exclude.iter().copied().chain(std::iter::once(id)).collect();
+        // widen the beam so the excluded ids don't eat into k
+        let ef = ef_search.unwrap_or(self.config.ef_search).max(k) + excluded.len();
+
+        let mut result: Vec<(f32, usize)> = self
+            .candidates(query, ef)
+            .into_iter()
+            .filter(|(_, n)| !excluded.contains(n))
+            .collect();
+        result.truncate(k);
+        Some(result)
+    }
+
+    // Row-major queries (n * dim floats), searched in parallel on the rayon pool.
+    // Results come back in query order.
+    pub fn search_batch(&self, queries: &[f32], k: usize, ef_search: Option<usize>) -> Vec<Vec<(f32, usize)>> {
+        let dim = self.vectors.dim;
+        assert_eq!(queries.len() % dim, 0, "Query buffer length must be a multiple of dim");
+        queries
+            .par_chunks_exact(dim)
+            .map(|query| self.search(query, k, ef_search))
+            .collect()
+    }
+
+    // Phase 1: Greedy search from top to 1, returns the entry point for layer 0
+    fn descend(&self, query: &[f32]) -> Option<usize> {
+        self.descend_traced(query, None)
+    }
+
+    fn descend_traced(&self, query: &[f32], mut stats: Option<&mut SearchStats>) -> Option<usize> {
+        let mut ep = self.entry_point?;
+        let top_level = self.layers.node_level(ep);
         for lc in (1..=top_level).rev() {
-            let w = self.search_layer(query, ep, 1, lc);
+            let (w, layer_stats) = self.search_layer_traced(query, ep, 1, lc, false);
+            if let Some(stats) = stats.as_deref_mut() {
+                stats.push_layer(layer_stats);
+            }
             if let Some((OrderedFloat(_), best_node)) = w.peek() {
                 ep = *best_node;
             }
         }
+        Some(ep)
+    }
 
-        let mut w = self.search_layer(query, ep, ef_search, 0);
+    // Everything within `radius` (same units as search distances, so squared for Euclidean).
+    // Works like search_layer on layer 0 but every candidate inside the radius is expanded
+    // regardless of ef, so the frontier keeps growing until no candidate within radius is left.
+    pub fn range_search(&self, query: &[f32], radius: f32, max_results: usize) -> Vec<(f32, usize)> {
+        let ep = match self.descend(query) {
+            Some(ep) => ep,
+            None => return Vec::new(),
+        };
+        let ef = self.config.ef_search;
 
+        let dist_ep = self.vectors.distance_to_query(ep, query);
+        let mut candidates: BinaryHeap<Reverse<(OrderedFloat<f32>, usize)>> = BinaryHeap::new();
+        // beam of the closest nodes seen so far, lets us cross gaps outside the radius
+        let mut beam: BinaryHeap<(OrderedFloat<f32>, usize)> = BinaryHeap::new();
+        let mut visited: HashSet<usize> = HashSet::new();
         let mut result = Vec::new();
-        while let Some((OrderedFloat(dist), node_id)) = w.pop() {
-            result.push((dist, node_id));
-            if result.len() >= k {}
+
+        visited.insert(ep);
+        candidates.push(Reverse((OrderedFloat(dist_ep), ep)));
+        beam.push((OrderedFloat(dist_ep), ep));
+        if dist_ep <= radius {
+            result.push((dist_ep, ep));
         }
 
-        result.reverse();
-        result.into_iter().take(k).collect()
+        while let Some(Reverse((OrderedFloat(dist_c), closest_candidate))) = candidates.pop() {
+            let (OrderedFloat(dist_worst), _) = *beam.peek().unwrap();
+            if dist_c > radius && dist_c > dist_worst && beam.len() >= ef {
+                break;
+            }
+
+            for &e in self.layers.get_neighbors(0, closest_candidate) {
+                if !visited.insert(e) {
+                    continue;
+                }
+                let dist_e = self.vectors.distance_to_query(e, query);
+                let (OrderedFloat(current_worst), _) = *beam.peek().unwrap();
+                if dist_e <= radius {
+                    result.push((dist_e, e));
+                }
+                if dist_e <= radius || dist_e < current_worst || beam.len() < ef {
+                    candidates.push(Reverse((OrderedFloat(dist_e), e)));
+                    beam.push((OrderedFloat(dist_e), e));
+                    if beam.len() > ef {
+                        beam.pop();
+                    }
+                }
+            }
+        }
+
+        result.sort_unstable_by_key(|&(d, id)| (OrderedFloat(d), id));
+        let mut result = self.to_external(result);
+        result.truncate(max_results);
+        result
+    }
+
+    pub fn brute_force_range_search(&self, query: &[f32], radius: f32) -> Vec<(f32, usize)> {
+        let mut results: Vec<_> = (0..self.vectors.len())
+            .into_par_iter()
+            .filter(|&i| !self.deleted[i])
+            .filter_map(|i| {
+                let dist = self.vectors.distance_to_query(i, query);
+                (dist <= radius).then_some((OrderedFloat(dist), i))
+            })
+            .collect();
+        results.sort_unstable();
+        self.to_external(results.into_iter().map(|(OrderedFloat(d), i)| (d, i)))
     }
 
     pub fn brute_force_search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
-        let n = self.vectors.data.len() / self.vectors.dim;
-        let mut results: Vec<_> = (0..n)
+        let mut results: Vec<_> = (0..self.vectors.len())
             .into_par_iter()
+            .filter(|&i| !self.deleted[i])
             .map(|i| {
-                let dist = self.vectors.squared_distance_to_query(i, query);
+                let dist = self.vectors.distance_to_query(i, query);
                 (OrderedFloat(dist), i)
             })
             .collect();
 
         if k >= results.len() {
              results.sort_unstable();
-             return results.into_iter().map(|(OrderedFloat(d), i)| (d, i)).collect();
+             return self.to_external(results.into_iter().map(|(OrderedFloat(d), i)| (d, i)));
         }
 
         results.select_nth_unstable(k);
         results.truncate(k);
         results.sort_unstable();
 
-        results.into_iter().map(|(OrderedFloat(d), i)| (d, i)).collect()
+        self.to_external(results.into_iter().map(|(OrderedFloat(d), i)| (d, i)))
     }
 
     pub fn select_neighbors_simple(
@@ -413,35 +842,653 @@ impl HNSW {
 }
 
 // Python Bindings
+use pyo3::buffer::PyBuffer;
 use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+// (id, vector, payload dict)
+type PyItem = (usize, Vec<f32>, Option<PyObject>);
+// (group key, [(distance, id)])
+type PyGroup = (PyObject, Vec<(f32, usize)>);
 
 #[pyclass]
 struct PyHNSW {
-    inner: HNSW,
+    // index="hnsw" or "flat", graph-only methods raise on a flat index
+    index: AnyIndex,
+    // for add_text / search_text, not saved
+    embedder: Option<Box<dyn Embedder>>,
+}
+
+// Python callable text -> list of floats used as an Embedder
+struct PyEmbedder {
+    callback: PyObject,
+    dim: usize,
+}
+
+impl Embedder for PyEmbedder {
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+        Python::with_gil(|py| {
+            self.callback
+                .call1(py, (text,))
+                .and_then(|v| v.extract::<Vec<f32>>(py))
+                .map_err(|e| e.to_string())
+        })
+    }
 }
 
 #[pymethods]
 impl PyHNSW {
+    // index="flat" skips the graph: exact search, the other params except metric are ignored.
+    // index="ivf" uses nlist / nprobe / pq instead of the graph params and needs train()
     #[new]
-    pub fn new(max_elements: usize, dim: usize, m: usize, ef_construction: usize) -> Self {
-        let mut hnsw = HNSW::new(max_elements, dim);
-        hnsw.m = m;
-        hnsw.ef_construction = ef_construction;
-        PyHNSW { inner: hnsw }
+    #[pyo3(signature = (max_elements, dim, m=16, ef_construction=64, ef_search=64, metric="euclidean", seed=None, index="hnsw", nlist=100, nprobe=8, pq=0))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        max_elements: usize,
+        dim: usize,
+        m: usize,
+        ef_construction: usize,
+        ef_search: usize,
+        metric: &str,
+        seed: Option<u64>,
+        index: &str,
+        nlist: usize,
+        nprobe: usize,
+        pq: usize,
+    ) -> PyResult<Self> {
+        let kind = IndexKind::parse(index).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let metric = Metric::parse(metric)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let mut config = HnswConfig::new(dim)
+            .max_elements(max_elements)
+            .metric(metric)
+            .m(m)
+            .ef_construction(ef_construction)
+            .ef_search(ef_search);
+        if let Some(seed) = seed {
+            config = config.seed(seed);
+        }
+        let inner = config
+            .build()
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let index = match kind {
+            IndexKind::Hnsw => AnyIndex::Hnsw(inner),
+            IndexKind::Flat => AnyIndex::Flat(FlatIndex::new(dim, metric)),
+            IndexKind::Ivf => {
+                let mut config = IvfConfig::new(dim).metric(metric).nlist(nlist).nprobe(nprobe).pq(pq);
+                if let Some(seed) = seed {
+                    config = config.seed(seed);
+                }
+                AnyIndex::Ivf(config.build().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?)
+            }
+        };
+        Ok(PyHNSW { index, embedder: None })
     }
 
-    fn insert(&mut self, vec: Vec<f32>, m: usize, m_max: usize, ef_construction: usize, m_l: f32) -> usize {
-        let id = self.inner.vectors.insert(&vec);
-        self.inner.insert(id, m, m_max, ef_construction, m_l);
-        id
+    // sparse: {dimension: value}, e.g. SPLADE output
+    #[pyo3(signature = (vec, payload=None, sparse=None))]
+    fn insert(&mut self, vec: Vec<f32>, payload: Option<&PyDict>, sparse: Option<&PyDict>) -> PyResult<usize> {
+        self.check_dim(&vec)?;
+        let sparse = sparse.map(sparse_from_py).transpose()?;
+        let id = match payload {
+            Some(payload) => self.inner_mut()?.insert_with_payload(&vec, payload_from_py(payload)?),
+            None if sparse.is_some() => self.inner_mut()?.insert(&vec),
+            None => self.index.insert(&vec),
+        };
+        if let Some(sparse) = sparse {
+            self.inner_mut()?.set_sparse(id, sparse);
+        }
+        self.enforce_memory_budget()?;
+        Ok(id)
+    }
+
+    // False if the id doesn't exist
+    fn set_sparse(&mut self, id: usize, sparse: &PyDict) -> PyResult<bool> {
+        Ok(self.inner_mut()?.set_sparse(id, sparse_from_py(sparse)?))
+    }
+
+    fn get_sparse(&self, py: Python, id: usize) -> PyResult<Option<PyObject>> {
+        self.inner()?
+            .get_sparse(id)
+            .map(|sparse| {
+                let dict = PyDict::new(py);
+                for (i, v) in sparse.iter() {
+                    dict.set_item(i, v)?;
+                }
+                Ok(dict.into())
+            })
+            .transpose()
     }
 
-    fn search(&self, query: Vec<f32>, k: usize, ef_search: usize) -> Vec<(f32, usize)> {
-        self.inner.search(&query, k, ef_search)
+    // Dot product over the sparse vectors, returns [(score, id)] highest first
+    fn sparse_search(&self, sparse: &PyDict, k: usize) -> PyResult<Vec<(f32, usize)>> {
+        Ok(self.inner()?.sparse_search(&sparse_from_py(sparse)?, k))
+    }
+
+    // Dense + sparse, fused like hybrid_search. alpha weights the dense side
+    #[pyo3(signature = (vector, sparse, k, alpha=0.5, fusion="rrf"))]
+    fn sparse_hybrid_search(&self, vector: Vec<f32>, sparse: &PyDict, k: usize, alpha: f32, fusion: &str) -> PyResult<Vec<(f32, usize)>> {
+        let fusion = Fusion::parse(fusion).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        self.check_dim(&vector)?;
+        Ok(self.inner()?.sparse_hybrid_search(&vector, &sparse_from_py(sparse)?, k, alpha, fusion))
+    }
+
+    fn get_vector(&self, id: usize) -> Option<Vec<f32>> {
+        self.index.get_vector(id).map(|v| v.to_vec())
+    }
+
+    fn get_vectors(&self, ids: Vec<usize>) -> Vec<Option<Vec<f32>>> {
+        ids.iter().map(|&id| self.index.get_vector(id).map(|v| v.to_vec())).collect()
+    }
+
+    fn get_payload(&self, py: Python, id: usize) -> PyResult<Option<PyObject>> {
+        self.inner()?.payload(id).map(|p| payload_to_py(py, p)).transpose()
+    }
+
+    fn set_payload(&mut self, id: usize, payload: &PyDict) -> PyResult<bool> {
+        Ok(self.inner_mut()?.set_payload(id, payload_from_py(payload)?))
+    }
+
+    // [(id, vector, payload or None)] for every live point, in id order
+    fn items(&self, py: Python) -> PyResult<Vec<PyItem>> {
+        self.inner()?
+            .iter()
+            .map(|(id, v, p)| Ok((id, v.to_vec(), p.map(|p| payload_to_py(py, p)).transpose()?)))
+            .collect()
+    }
+
+    // Writes .npy or .fvecs depending on the extension, returns the ids in row order
+    fn export(&self, path: String) -> PyResult<Vec<usize>> {
+        self.inner()?
+            .export(std::path::Path::new(&path))
+            .map_err(PyErr::new::<pyo3::exceptions::PyIOError, _>)
+    }
+
+    #[pyo3(signature = (query, k, ef_search=None))]
+    // ef_search is nprobe for an ivf index and ignored by a flat one, which is always exact
+    fn search(&self, query: Vec<f32>, k: usize, ef_search: Option<usize>) -> PyResult<Vec<(f32, usize)>> {
+        self.check_dim(&query)?;
+        Ok(self.index.search_with_depth(&query, k, ef_search))
+    }
+
+    // Moves the vectors to a mapped file at path, the graph stays in RAM. New vectors are
+    // buffered up to memory_budget_mb before they get appended to the file. hnsw only
+    #[pyo3(signature = (path, memory_budget_mb=64, prefetch=true))]
+    fn set_tier(&mut self, path: String, memory_budget_mb: usize, prefetch: bool) -> PyResult<()> {
+        let tier = TierConfig::new(path).memory_budget(memory_budget_mb << 20).prefetch(prefetch);
+        self.inner_mut()?
+            .set_tier(tier)
+            .map_err(PyErr::new::<pyo3::exceptions::PyIOError, _>)
+    }
+
+    // k-means over the vectors inserted so far, ivf only. Call it once after the bulk insert,
+    // again whenever the data drifted. Until then an ivf index searches exactly.
+    fn train(&mut self) -> PyResult<()> {
+        let err = self.unsupported();
+        let ivf = self.index.as_ivf_mut().ok_or(err)?;
+        ivf.train().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
     }
     
-    fn brute_force_search(&self, query: Vec<f32>, k: usize) -> Vec<(f32, usize)> {
-        self.inner.brute_force_search(&query, k)
+    // callback(text) -> list of dim floats, e.g. a sentence-transformers model
+    fn set_embedder(&mut self, callback: PyObject) {
+        let dim = self.index.dim();
+        self.embedder = Some(Box::new(PyEmbedder { callback, dim }));
+    }
+
+    // Offline embedder: words + character n-grams hashed into dim buckets
+    #[pyo3(signature = (min_n=3, max_n=5))]
+    fn use_hashing_embedder(&mut self, min_n: usize, max_n: usize) {
+        let dim = self.index.dim();
+        self.embedder = Some(Box::new(HashingEmbedder::new(dim).ngrams(min_n, max_n)));
+    }
+
+    // Offline embedder: averaged word vectors from a GloVe / word2vec text file
+    fn load_word_vectors(&mut self, path: String) -> PyResult<()> {
+        let vectors = WordVectors::load(std::path::Path::new(&path))
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        if vectors.dim != self.index.dim() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Word vector dimension mismatch: expected {}, got {}",
+                self.index.dim(), vectors.dim
+            )));
+        }
+        self.embedder = Some(Box::new(vectors));
+        Ok(())
+    }
+
+    // Embeds and inserts, the text goes into the "text" payload field
+    #[pyo3(signature = (text, payload=None))]
+    fn add_text(&mut self, text: &str, payload: Option<&PyDict>) -> PyResult<usize> {
+        let vec = self.embed_text(text)?;
+        let mut payload = match payload {
+            Some(payload) => payload_from_py(payload)?,
+            None => Payload::new(),
+        };
+        payload.insert("text".to_string(), PayloadValue::from(text));
+        let id = self.inner_mut()?.insert_with_payload(&vec, payload);
+        self.enforce_memory_budget()?;
+        Ok(id)
+    }
+
+    #[pyo3(signature = (text, k, ef_search=None))]
+    fn search_text(&self, text: &str, k: usize, ef_search: Option<usize>) -> PyResult<Vec<(f32, usize)>> {
+        let vec = self.embed_text(text)?;
+        Ok(self.inner()?.search(&vec, k, ef_search))
+    }
+
+    // query: text (needs an embedder) or a vector. token_counter: callable str -> int,
+    // defaults to counting whitespace separated words.
+    // Returns {"context": str, "tokens": int, "citations": [{"index", "ids", "source", "start", "end", "score", "truncated"}]}
+    #[pyo3(signature = (query, token_budget, k=5, token_counter=None))]
+    fn retrieve_context(
+        &self,
+        py: Python,
+        query: &PyAny,
+        token_budget: usize,
+        k: usize,
+        token_counter: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let vec = match query.extract::<&str>() {
+            Ok(text) => self.embed_text(text)?,
+            Err(_) => query.extract::<Vec<f32>>()?,
+        };
+        self.check_dim(&vec)?;
+
+        // the first exception from the python counter is kept and raised afterwards
+        let error: std::cell::RefCell<Option<PyErr>> = std::cell::RefCell::new(None);
+        let context = match &token_counter {
+            Some(callback) => {
+                let count = |text: &str| match callback.call1(py, (text,)).and_then(|n| n.extract::<usize>(py)) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        error.borrow_mut().get_or_insert(e);
+                        usize::MAX
+                    }
+                };
+                self.inner()?.retrieve_context(&vec, token_budget, k, &count)
+            }
+            None => self.inner()?.retrieve_context(&vec, token_budget, k, &WhitespaceTokens),
+        };
+        if let Some(e) = error.into_inner() {
+            return Err(e);
+        }
+
+        let citations = PyList::empty(py);
+        for c in &context.citations {
+            let dict = PyDict::new(py);
+            dict.set_item("index", c.index)?;
+            dict.set_item("ids", c.ids.clone())?;
+            dict.set_item("source", c.source.clone())?;
+            dict.set_item("start", c.start)?;
+            dict.set_item("end", c.end)?;
+            dict.set_item("score", c.score)?;
+            dict.set_item("truncated", c.truncated)?;
+            citations.append(dict)?;
+        }
+        let result = PyDict::new(py);
+        result.set_item("context", context.text)?;
+        result.set_item("tokens", context.tokens)?;
+        result.set_item("citations", citations)?;
+        Ok(result.into())
+    }
+
+    fn brute_force_search(&self, query: Vec<f32>, k: usize) -> PyResult<Vec<(f32, usize)>> {
+        self.check_dim(&query)?;
+        Ok(self.index.brute_force_search(&query, k))
+    }
+
+    // Returns (results, stats) where stats is a dict, per-layer details under "layers"
+    #[pyo3(signature = (query, k, ef_search=None))]
+    fn search_with_stats(&self, py: Python, query: Vec<f32>, k: usize, ef_search: Option<usize>) -> PyResult<(Vec<(f32, usize)>, PyObject)> {
+        self.check_dim(&query)?;
+        let (results, stats) = self.inner()?.search_with_stats(&query, k, ef_search);
+
+        let layers = PyList::empty(py);
+        for layer in &stats.layers {
+            let d = PyDict::new(py);
+            d.set_item("layer", layer.layer)?;
+            d.set_item("entry_point", layer.entry_point)?;
+            d.set_item("visited", layer.visited)?;
+            d.set_item("distance_evaluations", layer.distance_evaluations)?;
+            d.set_item("hops", layer.hops)?;
+            d.set_item("max_candidates", layer.max_candidates)?;
+            d.set_item("found", layer.found)?;
+            layers.append(d)?;
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("distance_evaluations", stats.distance_evaluations)?;
+        dict.set_item("hops", stats.hops)?;
+        dict.set_item("elapsed_us", stats.elapsed.as_secs_f64() * 1e6)?;
+        dict.set_item("layers", layers)?;
+        Ok((results, dict.into()))
+    }
+
+    // Neighbors of a stored point excluding itself, KeyError if the id doesn't exist
+    #[pyo3(signature = (id, k, ef_search=None, exclude=None))]
+    fn search_by_id(&self, id: usize, k: usize, ef_search: Option<usize>, exclude: Option<Vec<usize>>) -> PyResult<Vec<(f32, usize)>> {
+        self.inner()?
+            .search_by_id(id, k, ef_search, &exclude.unwrap_or_default())
+            .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyKeyError, _>(id))
+    }
+
+    // BM25 over the "text" payload field, returns [(score, id)] best first
+    fn text_search(&self, text: &str, k: usize) -> PyResult<Vec<(f32, usize)>> {
+        Ok(self.inner()?.text_search(text, k))
+    }
+
+    // fusion: "rrf" or "weighted"; alpha weights the vector side. Returns [(score, id)] best first
+    #[pyo3(signature = (text, vector, k, alpha=0.5, fusion="rrf"))]
+    fn hybrid_search(&self, text: &str, vector: Vec<f32>, k: usize, alpha: f32, fusion: &str) -> PyResult<Vec<(f32, usize)>> {
+        let fusion = Fusion::parse(fusion).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        self.check_dim(&vector)?;
+        Ok(self.inner()?.hybrid_search(text, &vector, k, alpha, fusion))
+    }
+
+    // At most per_group hits per distinct payload[group_by], returns [(key, [(distance, id), ...]), ...]
+    #[pyo3(signature = (query, group_by, groups=10, per_group=3))]
+    fn search_grouped(
+        &self,
+        py: Python,
+        query: Vec<f32>,
+        group_by: &str,
+        groups: usize,
+        per_group: usize,
+    ) -> PyResult<Vec<PyGroup>> {
+        self.check_dim(&query)?;
+        Ok(self.inner()?
+            .search_grouped(&query, group_by, groups, per_group)
+            .into_iter()
+            .map(|g| (value_to_py(py, &g.key), g.hits))
+            .collect())
+    }
+
+    // Diversified results: over-fetches fetch_k candidates and picks k with MMR
+    #[pyo3(signature = (query, k, fetch_k=None, lambda_mult=0.5))]
+    fn search_mmr(&self, query: Vec<f32>, k: usize, fetch_k: Option<usize>, lambda_mult: f32) -> PyResult<Vec<(f32, usize)>> {
+        self.check_dim(&query)?;
+        self.inner()?
+            .search_mmr(&query, k, fetch_k.unwrap_or(k * 4), lambda_mult)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    // strategy: "average" (average vector) or "best_score"
+    #[pyo3(signature = (positive, negative=None, k=10, strategy="average"))]
+    fn recommend(&self, positive: Vec<usize>, negative: Option<Vec<usize>>, k: usize, strategy: &str) -> PyResult<Vec<(f32, usize)>> {
+        let strategy = RecommendStrategy::parse(strategy).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        self.inner()?
+            .recommend(&positive, &negative.unwrap_or_default(), k, strategy)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    #[pyo3(signature = (positive, negative=None, k=10, strategy="average"))]
+    fn recommend_vectors(&self, positive: Vec<Vec<f32>>, negative: Option<Vec<Vec<f32>>>, k: usize, strategy: &str) -> PyResult<Vec<(f32, usize)>> {
+        let strategy = RecommendStrategy::parse(strategy).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        self.inner()?
+            .recommend_vectors(&positive, &negative.unwrap_or_default(), k, strategy)
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    // queries: float32 array of shape (n, dim), anything exposing the buffer protocol works.
+    // Returns (distances, ids) as numpy arrays of shape (n, k), rows padded with inf / -1
+    // when the index holds fewer than k vectors.
+    #[pyo3(signature = (queries, k, ef_search=None))]
+    fn search_batch(&self, py: Python, queries: &PyAny, k: usize, ef_search: Option<usize>) -> PyResult<(PyObject, PyObject)> {
+        let buffer = PyBuffer::<f32>::get(queries)?;
+        let dim = self.index.dim();
+        let valid_shape = match buffer.shape() {
+            [_, d] => *d == dim,
+            [n] => n % dim == 0,
+            _ => false,
+        };
+        if !valid_shape {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "queries must have shape (n, {}), got {:?}",
+                dim,
+                buffer.shape()
+            )));
+        }
+        let flat = buffer.to_vec(py)?;
+
+        let index = &self.index;
+        let results = py.allow_threads(|| index.search_batch_with_depth(&flat, k, ef_search));
+
+        let mut distances = Vec::with_capacity(results.len());
+        let mut ids = Vec::with_capacity(results.len());
+        for row in results {
+            let mut d_row = vec![f32::INFINITY; k];
+            let mut id_row = vec![-1i64; k];
+            for (j, (dist, id)) in row.into_iter().enumerate() {
+                d_row[j] = dist;
+                id_row[j] = id as i64;
+            }
+            distances.push(d_row);
+            ids.push(id_row);
+        }
+
+        let numpy = py.import("numpy")?;
+        let distances = numpy.call_method1("array", (distances, "float32"))?;
+        let ids = numpy.call_method1("array", (ids, "int64"))?;
+        // keep the (n, k) shape even for zero queries
+        let distances = distances.call_method1("reshape", (-1, k))?;
+        let ids = ids.call_method1("reshape", (-1, k))?;
+        Ok((distances.into(), ids.into()))
+    }
+
+    #[pyo3(signature = (query, radius, max_results=None))]
+    fn range_search(&self, query: Vec<f32>, radius: f32, max_results: Option<usize>) -> PyResult<Vec<(f32, usize)>> {
+        self.check_dim(&query)?;
+        Ok(self.inner()?.range_search(&query, radius, max_results.unwrap_or(usize::MAX)))
+    }
+
+    fn brute_force_range_search(&self, query: Vec<f32>, radius: f32) -> PyResult<Vec<(f32, usize)>> {
+        self.check_dim(&query)?;
+        Ok(self.inner()?.brute_force_range_search(&query, radius))
+    }
+
+    fn save(&self, path: String) -> PyResult<()> {
+        let bytes = self.index.to_bytes().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        std::fs::write(path, bytes).map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    #[staticmethod]
+    fn load(path: String) -> PyResult<Self> {
+         let bytes = std::fs::read(&path).map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+         // also validates an hnsw graph
+         let index = AnyIndex::from_bytes(&bytes).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+         Ok(PyHNSW { index, embedder: None })
+    }
+
+    // a flat index has no graph to break
+    fn validate(&self) -> PyResult<()> {
+        let Some(hnsw) = self.index.as_hnsw() else {
+            return Ok(());
+        };
+        hnsw.validate().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    // Structured report as a dict, print(db.diagnostics_report()) for the human version
+    fn diagnostics(&self, py: Python) -> PyResult<PyObject> {
+        let diag = self.inner()?.diagnostics();
+        let layers = PyList::empty(py);
+        for l in &diag.layers {
+            let d = PyDict::new(py);
+            d.set_item("layer", l.layer)?;
+            d.set_item("node_count", l.node_count)?;
+            d.set_item("edge_count", l.edge_count)?;
+            d.set_item("max_degree", l.max_degree)?;
+            d.set_item("degree_histogram", l.degree_histogram.clone())?;
+            d.set_item("zero_in_degree", l.zero_in_degree.clone())?;
+            d.set_item("unreachable", l.unreachable.clone())?;
+            d.set_item("dangling_edges", l.dangling_edges.clone())?;
+            d.set_item("duplicate_edges", l.duplicate_edges.clone())?;
+            d.set_item("self_loops", l.self_loops.clone())?;
+            d.set_item("over_capacity", l.over_capacity.clone())?;
+            layers.append(d)?;
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("vector_count", diag.vector_count)?;
+        dict.set_item("node_count", diag.node_count)?;
+        dict.set_item("entry_point", diag.entry_point)?;
+        dict.set_item("entry_level", diag.entry_level)?;
+        dict.set_item("unreachable", diag.unreachable().to_vec())?;
+        dict.set_item("errors", diag.errors())?;
+        dict.set_item("layers", layers)?;
+        Ok(dict.into())
+    }
+
+    fn diagnostics_report(&self) -> PyResult<String> {
+        Ok(self.inner()?.diagnostics().to_string())
+    }
+
+    fn delete(&mut self, id: usize) -> bool {
+        self.index.delete(id)
+    }
+
+    fn update(&mut self, id: usize, vec: Vec<f32>) -> PyResult<bool> {
+        self.check_dim(&vec)?;
+        let updated = self.inner_mut()?.update(id, &vec);
+        self.enforce_memory_budget()?;
+        Ok(updated)
+    }
+
+    // Physically removes deleted points, returns how many. save() afterwards for a smaller file.
+    // a flat index deletes in place, nothing to compact
+    fn compact(&mut self) -> PyResult<usize> {
+        match self.index.as_hnsw_mut() {
+            Some(hnsw) => hnsw.compact().map_err(PyErr::new::<pyo3::exceptions::PyIOError, _>),
+            None => Ok(0),
+        }
+    }
+
+    fn __len__(&self) -> usize {
+        self.index.len()
+    }
+
+    // Renumbers internal slots so graph neighbors sit next to each other in memory.
+    // strategy: "gorder" (default), "rcm" or "bfs". Ids don't change. Returns the mean
+    // slot distance between neighbors before and after.
+    #[pyo3(signature = (strategy="gorder"))]
+    fn reorder(&mut self, strategy: &str) -> PyResult<(f64, f64)> {
+        let strategy = ReorderStrategy::parse(strategy).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let hnsw = self.inner_mut()?;
+        let before = hnsw.average_edge_gap();
+        hnsw.reorder(strategy).map_err(PyErr::new::<pyo3::exceptions::PyIOError, _>)?;
+        Ok((before, hnsw.average_edge_gap()))
+    }
+
+    // Returns (unreachable_before, unreachable_after, relinked)
+    fn repair(&mut self) -> PyResult<(usize, usize, usize)> {
+        let report = self.inner_mut()?.repair();
+        Ok((report.unreachable_before, report.unreachable_after, report.relinked))
+    }
+
+    // Rebuilds the graph from the stored vectors, unspecified params keep their current value
+    #[pyo3(signature = (m=None, ef_construction=None, ef_search=None, seed=None))]
+    fn rebuild(&mut self, m: Option<usize>, ef_construction: Option<usize>, ef_search: Option<usize>, seed: Option<u64>) -> PyResult<()> {
+        let mut config = self.inner_mut()?.config.clone();
+        if let Some(m) = m {
+            config = config.m(m);
+        }
+        if let Some(ef_construction) = ef_construction {
+            config = config.ef_construction(ef_construction);
+        }
+        if let Some(ef_search) = ef_search {
+            config = config.ef_search(ef_search);
+        }
+        if let Some(seed) = seed {
+            config = config.seed(seed);
+        }
+        self.inner_mut()?.rebuild(config).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+}
+
+// Named vectors: several embedding spaces sharing ids, payloads and deletes
+#[pyclass]
+struct PyCollection {
+    inner: Collection,
+}
+
+#[pymethods]
+impl PyCollection {
+    #[new]
+    fn new() -> Self {
+        PyCollection { inner: Collection::new() }
+    }
+
+    #[pyo3(signature = (name, dim, max_elements=10_000, m=16, ef_construction=64, ef_search=64, metric="euclidean", seed=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn add_space(
+        &mut self,
+        name: &str,
+        dim: usize,
+        max_elements: usize,
+        m: usize,
+        ef_construction: usize,
+        ef_search: usize,
+        metric: &str,
+        seed: Option<u64>,
+    ) -> PyResult<()> {
+        let metric = Metric::parse(metric).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let mut config = HnswConfig::new(dim)
+            .max_elements(max_elements)
+            .metric(metric)
+            .m(m)
+            .ef_construction(ef_construction)
+            .ef_search(ef_search);
+        if let Some(seed) = seed {
+            config = config.seed(seed);
+        }
+        self.inner.add_space(name, config).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    fn spaces(&self) -> Vec<String> {
+        self.inner.spaces.keys().cloned().collect()
+    }
+
+    // vectors: {"title": [...], "body": [...]}, any subset of the spaces
+    #[pyo3(signature = (vectors, payload=None))]
+    fn insert(&mut self, vectors: std::collections::BTreeMap<String, Vec<f32>>, payload: Option<&PyDict>) -> PyResult<usize> {
+        let payload = payload.map(payload_from_py).transpose()?;
+        let pairs: Vec<(&str, &[f32])> = vectors.iter().map(|(name, v)| (name.as_str(), v.as_slice())).collect();
+        self.inner.insert(&pairs, payload).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    fn set_vector(&mut self, id: usize, space: &str, vec: Vec<f32>) -> PyResult<()> {
+        self.inner.set_vector(id, space, &vec).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    fn get_vector(&self, id: usize, space: &str) -> Option<Vec<f32>> {
+        self.inner.get_vector(id, space).map(|v| v.to_vec())
+    }
+
+    fn get_payload(&self, py: Python, id: usize) -> PyResult<Option<PyObject>> {
+        self.inner.payload(id).map(|p| payload_to_py(py, p)).transpose()
+    }
+
+    fn set_payload(&mut self, id: usize, payload: &PyDict) -> PyResult<bool> {
+        Ok(self.inner.set_payload(id, payload_from_py(payload)?))
+    }
+
+    #[pyo3(signature = (space, query, k, ef_search=None))]
+    fn search(&self, space: &str, query: Vec<f32>, k: usize, ef_search: Option<usize>) -> PyResult<Vec<(f32, usize)>> {
+        self.inner.search(space, &query, k, ef_search).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    fn delete(&mut self, id: usize) -> bool {
+        self.inner.delete(id)
+    }
+
+    fn compact(&mut self) -> PyResult<usize> {
+        self.inner.compact().map_err(PyErr::new::<pyo3::exceptions::PyIOError, _>)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
     }
 
     fn save(&self, path: String) -> PyResult<()> {
@@ -453,15 +1500,185 @@ impl PyHNSW {
 
     #[staticmethod]
     fn load(path: String) -> PyResult<Self> {
-         let bytes = std::fs::read(&path).map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
-         let inner = rkyv::from_bytes::<HNSW, Error>(&bytes)
-             .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-         Ok(PyHNSW { inner })
+        let bytes = std::fs::read(&path).map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        let inner = rkyv::from_bytes::<Collection, Error>(&bytes)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        inner.validate().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(PyCollection { inner })
+    }
+}
+
+// Multi-vector (ColBERT style) collection: every point is a list of token vectors
+#[pyclass]
+struct PyMultiHNSW {
+    inner: MultiVectorIndex,
+}
+
+#[pymethods]
+impl PyMultiHNSW {
+    // max_elements counts token vectors, not points
+    #[new]
+    #[pyo3(signature = (max_elements, dim, m=16, ef_construction=64, ef_search=64, metric="cosine", seed=None))]
+    pub fn new(
+        max_elements: usize,
+        dim: usize,
+        m: usize,
+        ef_construction: usize,
+        ef_search: usize,
+        metric: &str,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let metric = Metric::parse(metric).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        let mut config = HnswConfig::new(dim)
+            .max_elements(max_elements)
+            .metric(metric)
+            .m(m)
+            .ef_construction(ef_construction)
+            .ef_search(ef_search);
+        if let Some(seed) = seed {
+            config = config.seed(seed);
+        }
+        let inner = MultiVectorIndex::new(config).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(PyMultiHNSW { inner })
+    }
+
+    fn insert_multi(&mut self, vectors: Vec<Vec<f32>>) -> PyResult<usize> {
+        self.inner.insert_multi(&vectors).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    // [(maxsim score, id)] highest first
+    fn search_multi(&self, query: Vec<Vec<f32>>, k: usize) -> PyResult<Vec<(f32, usize)>> {
+        self.inner.search_multi(&query, k).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    fn get_multi(&self, id: usize) -> Option<Vec<Vec<f32>>> {
+        self.inner.get_multi(id).map(|vs| vs.into_iter().map(|v| v.to_vec()).collect())
+    }
+
+    fn delete(&mut self, id: usize) -> bool {
+        self.inner.delete(id)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn save(&self, path: String) -> PyResult<()> {
+        let bytes = rkyv::to_bytes::<Error>(&self.inner)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        std::fs::write(path, bytes).map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        Ok(())
+    }
+
+    #[staticmethod]
+    fn load(path: String) -> PyResult<Self> {
+        let bytes = std::fs::read(&path).map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+        let inner = rkyv::from_bytes::<MultiVectorIndex, Error>(&bytes)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        inner.tokens.validate().map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(PyMultiHNSW { inner })
+    }
+}
+
+impl PyHNSW {
+    fn inner(&self) -> PyResult<&HNSW> {
+        self.index.as_hnsw().ok_or_else(|| self.unsupported())
+    }
+
+    fn inner_mut(&mut self) -> PyResult<&mut HNSW> {
+        let err = self.unsupported();
+        self.index.as_hnsw_mut().ok_or(err)
+    }
+
+    // spills the RAM tail of a tiered index once it passes the budget
+    fn enforce_memory_budget(&mut self) -> PyResult<()> {
+        match self.index.as_hnsw_mut() {
+            Some(hnsw) => hnsw.enforce_memory_budget().map_err(PyErr::new::<pyo3::exceptions::PyIOError, _>),
+            None => Ok(()),
+        }
+    }
+
+    fn check_dim(&self, vec: &[f32]) -> PyResult<()> {
+        if vec.len() != self.index.dim() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "Vector dimension mismatch: expected {}, got {}",
+                self.index.dim(),
+                vec.len()
+            )));
+        }
+        Ok(())
+    }
+
+    fn unsupported(&self) -> PyErr {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "Not supported by a {} index, create it with index=\"hnsw\"",
+            self.index.kind().name()
+        ))
+    }
+
+    fn embed_text(&self, text: &str) -> PyResult<Vec<f32>> {
+        let embedder = self.embedder.as_ref().ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>("No embedder set, call set_embedder first")
+        })?;
+        let vec = embedder.embed(text).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        self.check_dim(&vec)?;
+        Ok(vec)
+    }
+}
+
+fn payload_from_py(dict: &PyDict) -> PyResult<Payload> {
+    let mut payload = Payload::new();
+    for (key, value) in dict.iter() {
+        let key: String = key.extract()?;
+        // bool first, python bools are ints too
+        let value = if let Ok(b) = value.downcast::<pyo3::types::PyBool>() {
+            PayloadValue::Bool(b.is_true())
+        } else if let Ok(i) = value.extract::<i64>() {
+            PayloadValue::Int(i)
+        } else if let Ok(f) = value.extract::<f64>() {
+            PayloadValue::Float(f)
+        } else if let Ok(s) = value.extract::<String>() {
+            PayloadValue::Str(s)
+        } else {
+            return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!(
+                "Unsupported payload value for key {:?}, use str, int, float or bool",
+                key
+            )));
+        };
+        payload.insert(key, value);
+    }
+    Ok(payload)
+}
+
+fn sparse_from_py(dict: &PyDict) -> PyResult<SparseVector> {
+    let pairs = dict
+        .iter()
+        .map(|(i, v)| Ok((i.extract::<u32>()?, v.extract::<f32>()?)))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(SparseVector::new(&pairs))
+}
+
+fn value_to_py(py: Python, value: &PayloadValue) -> PyObject {
+    match value {
+        PayloadValue::Str(s) => s.to_object(py),
+        PayloadValue::Int(i) => i.to_object(py),
+        PayloadValue::Float(f) => f.to_object(py),
+        PayloadValue::Bool(b) => b.to_object(py),
+    }
+}
+
+fn payload_to_py(py: Python, payload: &Payload) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    for (key, value) in payload {
+        dict.set_item(key, value_to_py(py, value))?;
     }
+    Ok(dict.into())
 }
 
 #[pymodule]
 fn photon_db(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyHNSW>()?;
+    m.add_class::<PyMultiHNSW>()?;
+    m.add_class::<PyCollection>()?;
     Ok(())
 }
@@ -1,9 +1,69 @@
-use photon_db::HNSW;
+use photon_db::{AnyIndex, Index, ReorderStrategy};
+use std::fs;
+use std::process::exit;
 // use serde::*;
 // use photon::PhotonDB;
 
+#[cfg(test)]
 const EPSILON: f32 = 1e-5;
 
+const USAGE: &str = "usage: photon <stats|compact|reorder> <index.pho> [bfs|rcm|gorder]";
+
+fn load(path: &str) -> Result<AnyIndex, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    AnyIndex::from_bytes(&bytes)
+}
+
+fn run(command: &str, path: &str, strategy: Option<&str>) -> Result<(), String> {
+    let mut index = load(path)?;
+    match command {
+        "stats" => match index.as_hnsw() {
+            Some(hnsw) => println!("{}", hnsw.diagnostics()),
+            None => println!("{} index: {} vectors, dim {}", index.kind().name(), index.len(), index.dim()),
+        },
+        "compact" => {
+            let before = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            let removed = match index.as_hnsw_mut() {
+                Some(hnsw) => hnsw.compact()?,
+                None => 0,
+            };
+            let bytes = index.to_bytes()?;
+            fs::write(path, &bytes).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+            println!(
+                "Removed {} deleted vectors, {} live. {} -> {} bytes",
+                removed,
+                index.len(),
+                before,
+                bytes.len()
+            );
+        }
+        "reorder" => {
+            let strategy = strategy.map_or(Ok(ReorderStrategy::default()), ReorderStrategy::parse)?;
+            let hnsw = index
+                .as_hnsw_mut()
+                .ok_or("Only an hnsw index has a graph to reorder")?;
+            let before = hnsw.average_edge_gap();
+            hnsw.reorder(strategy)?;
+            println!("Mean neighbor slot gap {:.1} -> {:.1}", before, hnsw.average_edge_gap());
+            fs::write(path, index.to_bytes()?).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 && !(args.len() == 4 && args[1] == "reorder") {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+    if let Err(e) = run(&args[1], &args[2], args.get(3).map(String::as_str)) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -22,8 +82,3 @@ mod tests {
         assert!((dist - 27.0).abs() < EPSILON);
     }
 }
-
-fn main() {
-    let hnsw = HNSW::new(10, 3);
-        
-}
@@ -0,0 +1,207 @@
+// Offline maintenance passes over an existing index.
+// repair() reconnects nodes that fell off the graph (select_neighbors_simple can drop
+// the only back-edge to a node), rebuild() throws the graph away and relinks the stored vectors,
+// compact() removes deleted points for good.
+
+use crate::{HnswConfig, HNSW};
+use ordered_float::OrderedFloat;
+
+// Upper bound on repair passes, relinking can orphan a pruned neighbor which the next pass picks up
+const MAX_REPAIR_PASSES: usize = 8;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepairReport {
+    // unreachable nodes on the base layer before / after the repair
+    pub unreachable_before: usize,
+    pub unreachable_after: usize,
+    // (node, layer) pairs that got new edges
+    pub relinked: usize,
+    pub passes: usize,
+}
+
+impl HNSW {
+    pub fn repair(&mut self) -> RepairReport {
+        let mut report = RepairReport {
+            unreachable_before: self.diagnostics().unreachable().len(),
+            ..Default::default()
+        };
+
+        while report.passes < MAX_REPAIR_PASSES {
+            let diag = self.diagnostics();
+            let broken: Vec<(usize, Vec<usize>)> = diag
+                .layers
+                .iter()
+                .rev()
+                .filter(|l| !l.unreachable.is_empty())
+                .map(|l| (l.layer, l.unreachable.clone()))
+                .collect();
+            if broken.is_empty() {
+                break;
+            }
+            report.passes += 1;
+            for (lc, nodes) in broken {
+                for node in nodes {
+                    if self.relink(node, lc) {
+                        report.relinked += 1;
+                    }
+                }
+            }
+        }
+
+        report.unreachable_after = self.diagnostics().unreachable().len();
+        report
+    }
+
+    // Finds `node`'s nearest reachable neighbors on layer `lc` and connects both ways.
+    // The back-edge is always kept, so `node` is reachable afterwards.
+    fn relink(&mut self, node: usize, lc: usize) -> bool {
+        let ep = match self.entry_point {
+            Some(ep) if ep != node => ep,
+            _ => return false,
+        };
+        let query = self.vectors.get(node).to_vec();
+
+        let mut ep = ep;
+        let top_level = self.layers.node_level(ep);
+        for layer in ((lc + 1)..=top_level).rev() {
+            if let Some((_, best)) = self.search_layer(&query, ep, 1, layer).peek() {
+                ep = *best;
+            }
+        }
+        let found = self.search_layer(&query, ep, self.config.ef_construction, lc);
+        let mut candidates: Vec<(OrderedFloat<f32>, usize)> = found.into_iter().filter(|&(_, n)| n != node).collect();
+        candidates.sort_unstable();
+        if candidates.is_empty() {
+            return false;
+        }
+
+        let m_max = if lc == 0 { self.config.m_max0 } else { self.config.m_max };
+        for &(_, n) in candidates.iter().take(self.config.m) {
+            if !self.layers.get_neighbors(lc, node).contains(&n) {
+                self.layers.add_edge(node, n, lc, true);
+            }
+            if !self.layers.get_neighbors(lc, n).contains(&node) {
+                self.layers.add_edge(n, node, lc, true);
+            }
+
+            let conn = self.layers.get_neighbors(lc, n);
+            if conn.len() > m_max {
+                let mut others: Vec<(OrderedFloat<f32>, usize)> = conn
+                    .iter()
+                    .filter(|&&c| c != node)
+                    .map(|&c| (OrderedFloat(self.vectors.distance(n, c)), c))
+                    .collect();
+                others.sort_unstable();
+                let mut kept: Vec<usize> = others.into_iter().take(m_max - 1).map(|(_, c)| c).collect();
+                kept.push(node);
+                self.layers.set_neighbors(lc, n, kept);
+            }
+        }
+
+        let conn = self.layers.get_neighbors(lc, node);
+        if conn.len() > m_max {
+            let mut own: Vec<(OrderedFloat<f32>, usize)> =
+                conn.iter().map(|&c| (OrderedFloat(self.vectors.distance(node, c)), c)).collect();
+            own.sort_unstable();
+            self.layers.set_neighbors(lc, node, own.into_iter().take(m_max).map(|(_, c)| c).collect());
+        }
+        true
+    }
+
+    // Fresh graph over the vectors already in the store, nothing gets re-embedded.
+    // `config.dim` must match the stored vectors.
+    pub fn rebuild(&mut self, config: HnswConfig) -> Result<(), String> {
+        config.validate()?;
+        if config.dim != self.vectors.dim {
+            return Err(format!(
+                "Dimension mismatch: index stores {}-d vectors, config has dim {}",
+                self.vectors.dim, config.dim
+            ));
+        }
+
+        // no point linking dead slots into the new graph
+        self.compact_store()?;
+
+        let mut fresh = HNSW::from_config(config);
+        // the whole store moves over, a tiered one keeps its file
+        std::mem::swap(&mut fresh.vectors, &mut self.vectors);
+        fresh.vectors.metric = fresh.config.metric;
+        fresh.ids = std::mem::take(&mut self.ids);
+        fresh.slots = std::mem::take(&mut self.slots);
+        fresh.deleted = std::mem::take(&mut self.deleted);
+        fresh.next_id = self.next_id;
+        fresh.payloads = std::mem::take(&mut self.payloads);
+        fresh.text_index = std::mem::take(&mut self.text_index);
+        fresh.sparse_index = std::mem::take(&mut self.sparse_index);
+        for q in 0..fresh.vectors.len() {
+            fresh.link(q);
+        }
+        *self = fresh;
+        Ok(())
+    }
+
+    // Physically drops soft deleted / superseded points. Slots are renumbered densely
+    // through the store, every layer and the entry point; external ids don't change.
+    // Returns the number of removed points. A tiered store that fails to rewrite its vector
+    // file returns the error with the index unchanged.
+    pub fn compact(&mut self) -> Result<usize, String> {
+        let removed = self.deleted_count();
+        if removed == 0 {
+            return Ok(0);
+        }
+        let remap = self.compact_store()?;
+        let remap_list = |list: &[usize]| -> Vec<usize> { list.iter().filter_map(|&n| remap[n]).collect() };
+
+        let base_layer = std::mem::take(&mut self.layers.base_layer);
+        self.layers.base_layer = base_layer
+            .iter()
+            .enumerate()
+            .filter(|(old, _)| remap[*old].is_some())
+            .map(|(_, list)| remap_list(list))
+            .collect();
+
+        for layer in self.layers.upper_layers.iter_mut() {
+            *layer = layer
+                .iter()
+                .filter_map(|(&old, list)| remap[old].map(|new| (new, remap_list(list))))
+                .collect();
+        }
+        while self.layers.upper_layers.last().is_some_and(|l| l.is_empty()) {
+            self.layers.upper_layers.pop();
+        }
+
+        // Deleted entry point -> hand over to any node on the highest remaining layer
+        self.entry_point = self.entry_point.and_then(|ep| remap[ep]).or_else(|| {
+            match self.layers.upper_layers.last() {
+                Some(top) => top.keys().next().copied(),
+                None => (!self.layers.base_layer.is_empty()).then_some(0),
+            }
+        });
+
+        // dropping nodes can cut paths, patch them up
+        self.repair();
+        Ok(removed)
+    }
+
+    // Squeezes deleted rows out of the store and id tables, leaves the graph alone.
+    // Returns old slot -> new slot.
+    pub(crate) fn compact_store(&mut self) -> Result<Vec<Option<usize>>, String> {
+        let mut remap = vec![None; self.vectors.len()];
+        let mut kept = Vec::with_capacity(self.slots.len());
+        let mut ids = Vec::with_capacity(self.slots.len());
+        for (slot, new) in remap.iter_mut().enumerate() {
+            if self.deleted[slot] {
+                continue;
+            }
+            *new = Some(ids.len());
+            kept.push(slot);
+            ids.push(self.ids[slot]);
+        }
+
+        self.vectors.keep_rows(&kept)?;
+        self.deleted = vec![false; ids.len()];
+        self.slots = ids.iter().enumerate().map(|(slot, &id)| (id, slot)).collect();
+        self.ids = ids;
+        Ok(remap)
+    }
+}
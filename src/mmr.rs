@@ -0,0 +1,53 @@
+// Maximal Marginal Relevance: over-fetch from the graph, then greedily pick the candidate
+// with the best lambda * relevance - (1 - lambda) * redundancy, where relevance is closeness
+// to the query and redundancy is closeness to the closest already picked result.
+// Everything is a negated distance so it works for every metric.
+
+use crate::HNSW;
+
+impl HNSW {
+    // lambda = 1.0 is plain top-k, lambda = 0.0 only cares about diversity.
+    // Results come in pick order as (distance to query, id).
+    pub fn search_mmr(&self, query: &[f32], k: usize, fetch_k: usize, lambda: f32) -> Result<Vec<(f32, usize)>, String> {
+        if !(0.0..=1.0).contains(&lambda) {
+            return Err(format!("lambda must be in [0, 1], got {}", lambda));
+        }
+        let fetch_k = fetch_k.max(k);
+        let mut pool: Vec<(f32, usize, usize)> = self
+            .candidates(query, fetch_k.max(self.config.ef_search))
+            .into_iter()
+            .take(fetch_k)
+            .map(|(d, id)| (d, id, self.slot_of(id).unwrap()))
+            .collect();
+
+        // redundancy[i] = distance from pool[i] to the closest selected point so far
+        let mut redundancy = vec![f32::INFINITY; pool.len()];
+        let mut selected = Vec::with_capacity(k);
+
+        while selected.len() < k && !pool.is_empty() {
+            let mut best = 0;
+            let mut best_score = f32::NEG_INFINITY;
+            for (i, &(dist, _, _)) in pool.iter().enumerate() {
+                let relevance = -dist;
+                let similarity = if redundancy[i].is_finite() { -redundancy[i] } else { 0.0 };
+                let score = if selected.is_empty() {
+                    relevance
+                } else {
+                    lambda * relevance - (1.0 - lambda) * similarity
+                };
+                if score > best_score {
+                    best_score = score;
+                    best = i;
+                }
+            }
+
+            let (dist, id, slot) = pool.swap_remove(best);
+            redundancy.swap_remove(best);
+            for (i, &(_, _, other)) in pool.iter().enumerate() {
+                redundancy[i] = redundancy[i].min(self.vectors.distance(slot, other));
+            }
+            selected.push((dist, id));
+        }
+        Ok(selected)
+    }
+}
@@ -0,0 +1,123 @@
+// Multi-vector points for late interaction (ColBERT): a point is a bag of token vectors.
+// Every token vector is its own node in an inner HNSW, search_multi collects the documents
+// owning the nearest tokens of every query token, then re-ranks them with exact MaxSim:
+// score(q, d) = sum over query tokens of the best similarity to any token of d.
+
+use crate::{HnswConfig, Metric, HNSW};
+use ordered_float::OrderedFloat;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Archive, Deserialize, Serialize, Debug, PartialEq)]
+pub struct MultiVectorIndex {
+    // one node per token vector
+    pub tokens: HNSW,
+    // point id -> token ids in the inner index, in insertion order
+    pub points: BTreeMap<usize, Vec<usize>>,
+    // token id -> point id
+    pub owner: BTreeMap<usize, usize>,
+    pub next_id: usize,
+}
+
+// higher = more similar: cosine similarity, dot product, or negated squared L2
+fn similarity(metric: Metric, a: &[f32], b: &[f32]) -> f32 {
+    let d = metric.distance(a, b);
+    match metric {
+        Metric::Cosine => 1.0 - d,
+        Metric::InnerProduct | Metric::Euclidean => -d,
+    }
+}
+
+impl MultiVectorIndex {
+    pub fn new(config: HnswConfig) -> Result<MultiVectorIndex, String> {
+        Ok(MultiVectorIndex {
+            tokens: config.build()?,
+            points: BTreeMap::new(),
+            owner: BTreeMap::new(),
+            next_id: 0,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn dim(&self) -> usize {
+        self.tokens.vectors.dim
+    }
+
+    fn check(&self, vectors: &[Vec<f32>]) -> Result<(), String> {
+        if vectors.is_empty() {
+            return Err("A multi-vector point needs at least one vector".to_string());
+        }
+        match vectors.iter().find(|v| v.len() != self.dim()) {
+            Some(v) => Err(format!("Vector dimension mismatch: expected {}, got {}", self.dim(), v.len())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn insert_multi(&mut self, vectors: &[Vec<f32>]) -> Result<usize, String> {
+        self.check(vectors)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        let token_ids: Vec<usize> = vectors.iter().map(|v| self.tokens.insert(v)).collect();
+        for &t in &token_ids {
+            self.owner.insert(t, id);
+        }
+        self.points.insert(id, token_ids);
+        Ok(id)
+    }
+
+    pub fn get_multi(&self, id: usize) -> Option<Vec<&[f32]>> {
+        let token_ids = self.points.get(&id)?;
+        token_ids.iter().map(|&t| self.tokens.get_vector(t)).collect()
+    }
+
+    pub fn delete(&mut self, id: usize) -> bool {
+        let Some(token_ids) = self.points.remove(&id) else {
+            return false;
+        };
+        for t in token_ids {
+            self.tokens.delete(t);
+            self.owner.remove(&t);
+        }
+        true
+    }
+
+    // Exact late-interaction score of a stored point, None if it doesn't exist
+    pub fn maxsim(&self, query: &[Vec<f32>], id: usize) -> Option<f32> {
+        let doc = self.get_multi(id)?;
+        let metric = self.tokens.vectors.metric;
+        Some(
+            query
+                .iter()
+                .map(|q| doc.iter().map(|d| similarity(metric, q, d)).fold(f32::NEG_INFINITY, f32::max))
+                .sum(),
+        )
+    }
+
+    // (MaxSim score, id), highest first. Candidates are the owners of the ef_search
+    // nearest tokens of every query token, so recall follows ef_search like plain search.
+    pub fn search_multi(&self, query: &[Vec<f32>], k: usize) -> Result<Vec<(f32, usize)>, String> {
+        self.check(query)?;
+        let ef = self.tokens.config.ef_search.max(k);
+        let mut candidates = HashSet::new();
+        for q in query {
+            for (_, token) in self.tokens.candidates(q, ef) {
+                candidates.extend(self.owner.get(&token));
+            }
+        }
+
+        let mut scored: Vec<(f32, usize)> = candidates
+            .into_iter()
+            .filter_map(|id| Some((self.maxsim(query, id)?, id)))
+            .collect();
+        scored.sort_unstable_by_key(|&(s, id)| (std::cmp::Reverse(OrderedFloat(s)), id));
+        scored.truncate(k);
+        Ok(scored)
+    }
+}
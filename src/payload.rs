@@ -0,0 +1,80 @@
+// Per-point metadata, keyed by external id on the index so it survives compact().
+// Kept to a few scalar types so it archives with rkyv like the rest of the index.
+
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum PayloadValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl PayloadValue {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PayloadValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            PayloadValue::Int(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            PayloadValue::Float(f) => Some(*f),
+            PayloadValue::Int(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PayloadValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayloadValue::Str(s) => write!(f, "{}", s),
+            PayloadValue::Int(i) => write!(f, "{}", i),
+            PayloadValue::Float(x) => write!(f, "{}", x),
+            PayloadValue::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+impl From<&str> for PayloadValue {
+    fn from(s: &str) -> Self {
+        PayloadValue::Str(s.to_string())
+    }
+}
+
+impl From<String> for PayloadValue {
+    fn from(s: String) -> Self {
+        PayloadValue::Str(s)
+    }
+}
+
+impl From<i64> for PayloadValue {
+    fn from(i: i64) -> Self {
+        PayloadValue::Int(i)
+    }
+}
+
+impl From<f64> for PayloadValue {
+    fn from(f: f64) -> Self {
+        PayloadValue::Float(f)
+    }
+}
+
+impl From<bool> for PayloadValue {
+    fn from(b: bool) -> Self {
+        PayloadValue::Bool(b)
+    }
+}
+
+pub type Payload = BTreeMap<String, PayloadValue>;
@@ -1,9 +1,11 @@
 // As of now implementing the Ram only solution and i will implement complete disk zero copy persistance when completed
 // Os internals , how database works ?? watch some tuts .
 
-use crate::HNSW;
+use crate::context::{RetrievedContext, TokenCounter, WhitespaceTokens};
+use crate::embed::Embedder;
+use crate::index::{AnyIndex, Index, IndexKind};
+use crate::{FlatIndex, HnswConfig, IvfConfig, Metric, Payload, PayloadValue, HNSW};
 // use memmap2::*;
-use rkyv::rancor::Error;
 // use rkyv::Archive;
 use std::io::Write;
 // use std::path::Path;
@@ -12,18 +14,31 @@ use std::{fs::File, path::PathBuf};
 
 const DB_NAME: &str = "main_hnsw_database.pho";
 
-#[derive(Debug)]
 pub struct PhotonDB {
-    pub hnsw: HNSW,
+    // HNSW or flat, the kind is stored in the file
+    pub index: AnyIndex,
     pub dim: usize,
     pub path: PathBuf,
+    // used by add_text / search_text, not saved with the index
+    pub embedder: Option<Box<dyn Embedder>>,
+}
+
+impl std::fmt::Debug for PhotonDB {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PhotonDB")
+            .field("index", &self.index)
+            .field("dim", &self.dim)
+            .field("path", &self.path)
+            .field("embedder", &self.embedder.as_ref().map(|e| e.dim()))
+            .finish()
+    }
 }
 
 impl PhotonDB {
     pub fn save(&self) -> Result<bool, String> {
         let mut file = File::create(&self.path).expect("Failed to create db file");
         // let mut file = File::open(&self.path).unwrap();
-        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&self.hnsw).unwrap();
+        let bytes = self.index.to_bytes()?;
         file.write_all(&bytes).expect("Error in writing");
         Ok(true)
     }
@@ -37,12 +52,13 @@ impl PhotonDB {
             let mut bytes = Vec::new();
             file.read_to_end(&mut bytes).expect("Failed to read file");
 
-            let hnsw = rkyv::from_bytes::<HNSW, Error>(&bytes).unwrap();
+            let index = AnyIndex::from_bytes(&bytes)?;
 
             Ok(PhotonDB {
-                hnsw,
+                index,
                 dim,
                 path: db_path,
+                embedder: None,
             })
         } else {
             // println!("Error: {:?}", db_path);
@@ -55,19 +71,149 @@ impl PhotonDB {
         let db_path = dir_path.join(DB_NAME);
 
         Ok(PhotonDB {
-            hnsw: HNSW::new(max_elements, dim),
+            index: HNSW::new(max_elements, dim).into(),
+            dim,
+            path: db_path,
+            embedder: None,
+        })
+    }
+
+    pub fn create_with_config(path: PathBuf, config: HnswConfig) -> Result<PhotonDB, String> {
+        let dir_path = path.parent().unwrap();
+        let db_path = dir_path.join(DB_NAME);
+        let dim = config.dim;
+
+        Ok(PhotonDB {
+            index: config.build()?.into(),
             dim,
             path: db_path,
+            embedder: None,
         })
     }
 
-    pub fn add(&mut self, vec: &[f32]) {
-        let id = self.hnsw.vectors.insert(vec);
-        let m = self.hnsw.m;
-        let m_max = m;
-        let ef_construction = self.hnsw.ef_construction;
-        let m_l = 1.0 / (m as f32).ln();
+    // Exact search only, no graph. Payloads / text / context need an HNSW index
+    pub fn create_flat(path: PathBuf, dim: usize, metric: Metric) -> Result<PhotonDB, String> {
+        let dir_path = path.parent().unwrap();
+        let db_path = dir_path.join(DB_NAME);
+
+        Ok(PhotonDB {
+            index: FlatIndex::new(dim, metric).into(),
+            dim,
+            path: db_path,
+            embedder: None,
+        })
+    }
+
+    // IVF, searches exactly until train() is called after the bulk insert
+    pub fn create_ivf(path: PathBuf, config: IvfConfig) -> Result<PhotonDB, String> {
+        let dir_path = path.parent().unwrap();
+        let db_path = dir_path.join(DB_NAME);
+        let dim = config.dim;
+
+        Ok(PhotonDB {
+            index: config.build()?.into(),
+            dim,
+            path: db_path,
+            embedder: None,
+        })
+    }
+
+    pub fn train(&mut self) -> Result<(), String> {
+        let kind = self.kind();
+        self.index
+            .as_ivf_mut()
+            .ok_or_else(|| format!("Only an ivf index needs training, this one is {}", kind.name()))?
+            .train()
+    }
+
+    pub fn kind(&self) -> IndexKind {
+        self.index.kind()
+    }
+
+    pub fn hnsw(&self) -> Result<&HNSW, String> {
+        self.index
+            .as_hnsw()
+            .ok_or_else(|| format!("Not supported by a {} index", self.kind().name()))
+    }
+
+    pub fn hnsw_mut(&mut self) -> Result<&mut HNSW, String> {
+        let kind = self.kind();
+        self.index
+            .as_hnsw_mut()
+            .ok_or_else(|| format!("Not supported by a {} index", kind.name()))
+    }
+
+    pub fn add(&mut self, vec: &[f32]) -> usize {
+        self.index.insert(vec)
+    }
+
+    pub fn add_with_payload(&mut self, vec: &[f32], payload: Payload) -> Result<usize, String> {
+        Ok(self.hnsw_mut()?.insert_with_payload(vec, payload))
+    }
+
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        self.index.search(query, k)
+    }
+
+    pub fn set_embedder(&mut self, embedder: Box<dyn Embedder>) -> Result<(), String> {
+        if embedder.dim() != self.dim {
+            return Err(format!("Embedder dimension mismatch: expected {}, got {}", self.dim, embedder.dim()));
+        }
+        self.embedder = Some(embedder);
+        Ok(())
+    }
+
+    pub fn embed(&self, text: &str) -> Result<Vec<f32>, String> {
+        let embedder = self.embedder.as_ref().ok_or("No embedder set, call set_embedder first")?;
+        let vec = embedder.embed(text)?;
+        if vec.len() != self.dim {
+            return Err(format!("Vector dimension mismatch: expected {}, got {}", self.dim, vec.len()));
+        }
+        Ok(vec)
+    }
+
+    // The text is kept in the "text" payload field, so it is also searchable with BM25
+    pub fn add_text(&mut self, text: &str) -> Result<usize, String> {
+        self.add_text_with_payload(text, Payload::new())
+    }
+
+    pub fn add_text_with_payload(&mut self, text: &str, mut payload: Payload) -> Result<usize, String> {
+        let vec = self.embed(text)?;
+        payload.insert("text".to_string(), PayloadValue::from(text));
+        self.add_with_payload(&vec, payload)
+    }
+
+    pub fn search_text(&self, text: &str, k: usize) -> Result<Vec<(f32, usize)>, String> {
+        Ok(self.index.search(&self.embed(text)?, k))
+    }
+
+    // Prompt-ready context for a text query, tokens counted as whitespace separated words
+    pub fn retrieve_context(&self, query: &str, token_budget: usize, k: usize) -> Result<RetrievedContext, String> {
+        self.retrieve_context_with(query, token_budget, k, &WhitespaceTokens)
+    }
+
+    pub fn retrieve_context_with(
+        &self,
+        query: &str,
+        token_budget: usize,
+        k: usize,
+        counter: &dyn TokenCounter,
+    ) -> Result<RetrievedContext, String> {
+        Ok(self.hnsw()?.retrieve_context(&self.embed(query)?, token_budget, k, counter))
+    }
+
+    pub fn delete(&mut self, id: usize) -> bool {
+        self.index.delete(id)
+    }
 
-        self.hnsw.insert(id, m, m_max, ef_construction, m_l);
+    // Drops deleted points and rewrites the file, returns how many were removed.
+    // Flat and IVF indexes delete in place, so there is never anything to drop.
+    pub fn compact(&mut self) -> Result<usize, String> {
+        let removed = match self.index.as_hnsw_mut() {
+            Some(hnsw) => hnsw.compact()?,
+            None => 0,
+        };
+        self.save()?;
+        Ok(removed)
     }
 }
@@ -0,0 +1,129 @@
+// Recommendations from liked / disliked examples.
+// AverageVector: one graph search towards avg(pos) + (avg(pos) - avg(neg)).
+// BestScore: one search per positive, then every candidate is scored by its closest
+// positive and pushed to the back if some negative is even closer.
+
+use crate::HNSW;
+use ordered_float::OrderedFloat;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecommendStrategy {
+    #[default]
+    AverageVector,
+    BestScore,
+}
+
+impl RecommendStrategy {
+    pub fn parse(name: &str) -> Result<RecommendStrategy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "average" | "average_vector" => Ok(RecommendStrategy::AverageVector),
+            "best" | "best_score" => Ok(RecommendStrategy::BestScore),
+            other => Err(format!("Unknown recommend strategy: {}", other)),
+        }
+    }
+}
+
+fn mean(vectors: &[&[f32]], dim: usize) -> Vec<f32> {
+    let mut sum = vec![0.0; dim];
+    for v in vectors {
+        for (s, x) in sum.iter_mut().zip(v.iter()) {
+            *s += x;
+        }
+    }
+    let n = vectors.len().max(1) as f32;
+    sum.iter_mut().for_each(|s| *s /= n);
+    sum
+}
+
+impl HNSW {
+    // Example ids are never returned. Err if an id doesn't exist or there are no positives.
+    pub fn recommend(
+        &self,
+        positive_ids: &[usize],
+        negative_ids: &[usize],
+        k: usize,
+        strategy: RecommendStrategy,
+    ) -> Result<Vec<(f32, usize)>, String> {
+        let lookup = |ids: &[usize]| -> Result<Vec<&[f32]>, String> {
+            ids.iter()
+                .map(|&id| self.get_vector(id).ok_or_else(|| format!("Unknown id: {}", id)))
+                .collect()
+        };
+        let positive = lookup(positive_ids)?;
+        let negative = lookup(negative_ids)?;
+        let exclude: Vec<usize> = positive_ids.iter().chain(negative_ids).copied().collect();
+        self.recommend_from(&positive, &negative, k, strategy, &exclude)
+    }
+
+    // Same thing with raw vectors instead of stored points
+    pub fn recommend_vectors(
+        &self,
+        positive: &[Vec<f32>],
+        negative: &[Vec<f32>],
+        k: usize,
+        strategy: RecommendStrategy,
+    ) -> Result<Vec<(f32, usize)>, String> {
+        let dim = self.vectors.dim;
+        if let Some(v) = positive.iter().chain(negative).find(|v| v.len() != dim) {
+            return Err(format!("Vector dimension mismatch: expected {}, got {}", dim, v.len()));
+        }
+        let positive: Vec<&[f32]> = positive.iter().map(|v| v.as_slice()).collect();
+        let negative: Vec<&[f32]> = negative.iter().map(|v| v.as_slice()).collect();
+        self.recommend_from(&positive, &negative, k, strategy, &[])
+    }
+
+    fn recommend_from(
+        &self,
+        positive: &[&[f32]],
+        negative: &[&[f32]],
+        k: usize,
+        strategy: RecommendStrategy,
+        exclude: &[usize],
+    ) -> Result<Vec<(f32, usize)>, String> {
+        if positive.is_empty() {
+            return Err("At least one positive example is required".to_string());
+        }
+        let excluded: HashSet<usize> = exclude.iter().copied().collect();
+        let ef = self.config.ef_search.max(k) + excluded.len();
+
+        match strategy {
+            RecommendStrategy::AverageVector => {
+                let dim = self.vectors.dim;
+                let avg_pos = mean(positive, dim);
+                let target: Vec<f32> = if negative.is_empty() {
+                    avg_pos
+                } else {
+                    let avg_neg = mean(negative, dim);
+                    avg_pos.iter().zip(&avg_neg).map(|(p, n)| p + (p - n)).collect()
+                };
+                let mut result: Vec<(f32, usize)> = self
+                    .candidates(&target, ef)
+                    .into_iter()
+                    .filter(|(_, id)| !excluded.contains(id))
+                    .collect();
+                result.truncate(k);
+                Ok(result)
+            }
+            RecommendStrategy::BestScore => {
+                let mut seen = HashSet::new();
+                let mut scored = Vec::new();
+                for p in positive {
+                    for (_, id) in self.candidates(p, ef) {
+                        if excluded.contains(&id) || !seen.insert(id) {
+                            continue;
+                        }
+                        let v = self.get_vector(id).unwrap();
+                        let metric = self.vectors.metric;
+                        let best_pos = positive.iter().map(|p| metric.distance(v, p)).fold(f32::INFINITY, f32::min);
+                        let best_neg = negative.iter().map(|n| metric.distance(v, n)).fold(f32::INFINITY, f32::min);
+                        // closer to a negative than to any positive -> behind every clean candidate
+                        scored.push((best_neg <= best_pos, OrderedFloat(best_pos), id));
+                    }
+                }
+                scored.sort_unstable();
+                Ok(scored.into_iter().take(k).map(|(_, OrderedFloat(d), id)| (d, id)).collect())
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// Slot reordering for cache locality. Slots are handed out in insertion order, so the
+// neighbors of a node end up anywhere in the store and every hop of a search is a cache
+// (or with a tier, page) miss. reorder() picks a new slot order where base layer neighbors
+// sit close together, then permutes the vector rows and renumbers every adjacency list.
+// External ids don't change, only the ids / slots tables get permuted with the rows.
+
+use crate::HNSW;
+use std::collections::{BinaryHeap, VecDeque};
+
+// nodes that count as "recently placed" for Gorder's scoring
+const GORDER_WINDOW: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReorderStrategy {
+    // breadth-first from the entry point
+    Bfs,
+    // reverse Cuthill-McKee: BFS from a low degree node, lowest degree neighbors first, reversed
+    Rcm,
+    // greedy: next node is the one sharing the most edges / in-neighbors with the last few placed
+    #[default]
+    Gorder,
+}
+
+impl ReorderStrategy {
+    pub fn parse(name: &str) -> Result<ReorderStrategy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "bfs" => Ok(ReorderStrategy::Bfs),
+            "rcm" => Ok(ReorderStrategy::Rcm),
+            "gorder" => Ok(ReorderStrategy::Gorder),
+            other => Err(format!("Unknown reorder strategy: {}", other)),
+        }
+    }
+}
+
+impl HNSW {
+    // Returns old slot -> new slot. Deleted slots are kept, compact() first to drop them.
+    // A tiered store gets its vector file rewritten first, on error nothing has changed.
+    // After that a .pho saved before won't load against the file anymore, save again.
+    pub fn reorder(&mut self, strategy: ReorderStrategy) -> Result<Vec<usize>, String> {
+        let order = match strategy {
+            ReorderStrategy::Bfs => self.bfs_order(),
+            ReorderStrategy::Rcm => self.rcm_order(),
+            ReorderStrategy::Gorder => self.gorder_order(),
+        };
+        let mut remap = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            remap[old] = new;
+        }
+
+        self.vectors.keep_rows(&order)?;
+        self.ids = order.iter().map(|&old| self.ids[old]).collect();
+        self.deleted = order.iter().map(|&old| self.deleted[old]).collect();
+        for slot in self.slots.values_mut() {
+            *slot = remap[*slot];
+        }
+
+        let renumber = |list: &[usize]| -> Vec<usize> { list.iter().map(|&n| remap[n]).collect() };
+        self.layers.base_layer = order.iter().map(|&old| renumber(&self.layers.base_layer[old])).collect();
+        for layer in self.layers.upper_layers.iter_mut() {
+            *layer = layer.iter().map(|(&old, list)| (remap[old], renumber(list))).collect();
+        }
+        self.entry_point = self.entry_point.map(|ep| remap[ep]);
+        Ok(remap)
+    }
+
+    // Mean |slot(a) - slot(b)| over base layer edges, lower = neighbors closer in memory
+    pub fn average_edge_gap(&self) -> f64 {
+        let (mut sum, mut edges) = (0u64, 0u64);
+        for (node, list) in self.layers.base_layer.iter().enumerate() {
+            for &n in list {
+                sum += node.abs_diff(n) as u64;
+                edges += 1;
+            }
+        }
+        if edges == 0 {
+            return 0.0;
+        }
+        sum as f64 / edges as f64
+    }
+
+    // Start points for graph walks: the entry point first, then every slot in order so
+    // parts of the graph it can't reach still get placed
+    fn walk_starts(&self) -> impl Iterator<Item = usize> + '_ {
+        self.entry_point.into_iter().chain(0..self.layers.base_layer.len())
+    }
+
+    fn bfs_order(&self) -> Vec<usize> {
+        let n = self.layers.base_layer.len();
+        let mut placed = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        let mut queue = VecDeque::new();
+        for start in self.walk_starts() {
+            if placed[start] {
+                continue;
+            }
+            placed[start] = true;
+            queue.push_back(start);
+            while let Some(node) = queue.pop_front() {
+                order.push(node);
+                for &nb in &self.layers.base_layer[node] {
+                    if !placed[nb] {
+                        placed[nb] = true;
+                        queue.push_back(nb);
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    fn rcm_order(&self) -> Vec<usize> {
+        let n = self.layers.base_layer.len();
+        let degree = |node: usize| self.layers.base_layer[node].len();
+        // lowest degree first, a cheap stand-in for a pseudo-peripheral start node
+        let mut starts: Vec<usize> = (0..n).collect();
+        starts.sort_by_key(|&node| (degree(node), node));
+
+        let mut placed = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        let mut queue = VecDeque::new();
+        for start in starts {
+            if placed[start] {
+                continue;
+            }
+            placed[start] = true;
+            queue.push_back(start);
+            while let Some(node) = queue.pop_front() {
+                order.push(node);
+                let mut next: Vec<usize> = self.layers.base_layer[node].iter().copied().filter(|&nb| !placed[nb]).collect();
+                next.sort_by_key(|&nb| (degree(nb), nb));
+                next.dedup();
+                for nb in next {
+                    placed[nb] = true;
+                    queue.push_back(nb);
+                }
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    // Gorder-style greedy placement. score[v] = for every node u in the window of the last
+    // GORDER_WINDOW placed: edges between u and v (either direction) + in-neighbors u and v share.
+    // The best unplaced score is kept in a lazy max-heap, stale entries are skipped on pop.
+    fn gorder_order(&self) -> Vec<usize> {
+        let n = self.layers.base_layer.len();
+        let mut incoming: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (node, list) in self.layers.base_layer.iter().enumerate() {
+            for &nb in list {
+                incoming[nb].push(node);
+            }
+        }
+
+        let mut score = vec![0i64; n];
+        let mut placed = vec![false; n];
+        let mut heap: BinaryHeap<(i64, std::cmp::Reverse<usize>)> = BinaryHeap::new();
+        let mut order = Vec::with_capacity(n);
+        let mut starts = self.walk_starts();
+
+        // every node whose score changes when u enters (+1) or leaves (-1) the window
+        let touch = |u: usize, delta: i64, score: &mut [i64], heap: &mut BinaryHeap<_>, placed: &[bool]| {
+            let out = &self.layers.base_layer[u];
+            let siblings = incoming[u].iter().flat_map(|&x| self.layers.base_layer[x].iter());
+            for &v in out.iter().chain(&incoming[u]).chain(siblings) {
+                if v == u || placed[v] {
+                    continue;
+                }
+                score[v] += delta;
+                if delta > 0 {
+                    heap.push((score[v], std::cmp::Reverse(v)));
+                }
+            }
+        };
+
+        while order.len() < n {
+            let mut next = None;
+            while let Some((s, std::cmp::Reverse(v))) = heap.pop() {
+                if placed[v] {
+                    continue;
+                }
+                if s != score[v] {
+                    // score dropped since this entry was pushed
+                    heap.push((score[v], std::cmp::Reverse(v)));
+                    continue;
+                }
+                if s > 0 {
+                    next = Some(v);
+                }
+                break;
+            }
+            let node = match next {
+                Some(v) => v,
+                None => starts.find(|&s| !placed[s]).unwrap(),
+            };
+            placed[node] = true;
+            order.push(node);
+            touch(node, 1, &mut score, &mut heap, &placed);
+            if order.len() > GORDER_WINDOW {
+                let left = order[order.len() - 1 - GORDER_WINDOW];
+                touch(left, -1, &mut score, &mut heap, &placed);
+            }
+        }
+        order
+    }
+}
@@ -0,0 +1,161 @@
+// Sparse vectors (SPLADE, BM25-style term weights, ...) next to the dense graph.
+// An inverted list per dimension, scored by dot product. Keyed by external id like
+// payloads, lives inside HNSW so it is saved in the same .pho file.
+
+use crate::text::{fuse, Fusion};
+use crate::HNSW;
+use ordered_float::OrderedFloat;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct SparseVector {
+    // sorted, unique
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+impl SparseVector {
+    // Any order, duplicates are summed and zeros dropped
+    pub fn new(pairs: &[(u32, f32)]) -> Self {
+        let mut merged: BTreeMap<u32, f32> = BTreeMap::new();
+        for &(i, v) in pairs {
+            *merged.entry(i).or_insert(0.0) += v;
+        }
+        merged.retain(|_, v| *v != 0.0);
+        SparseVector {
+            indices: merged.keys().copied().collect(),
+            values: merged.values().copied().collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, f32)> + '_ {
+        self.indices.iter().copied().zip(self.values.iter().copied())
+    }
+
+    pub fn dot(&self, other: &SparseVector) -> f32 {
+        let (mut i, mut j, mut sum) = (0, 0, 0.0);
+        while i < self.indices.len() && j < other.indices.len() {
+            match self.indices[i].cmp(&other.indices[j]) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    sum += self.values[i] * other.values[j];
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        sum
+    }
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq, Default)]
+pub struct SparseIndex {
+    // dimension -> [(external id, value)]
+    pub postings: BTreeMap<u32, Vec<(usize, f32)>>,
+    // external id -> vector, needed to clean up the postings on remove
+    pub vectors: BTreeMap<usize, SparseVector>,
+}
+
+impl SparseIndex {
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&SparseVector> {
+        self.vectors.get(&id)
+    }
+
+    // Replaces whatever was stored for id
+    pub fn insert(&mut self, id: usize, vector: SparseVector) {
+        self.remove(id);
+        for (i, v) in vector.iter() {
+            self.postings.entry(i).or_default().push((id, v));
+        }
+        self.vectors.insert(id, vector);
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<SparseVector> {
+        let vector = self.vectors.remove(&id)?;
+        for i in &vector.indices {
+            if let Some(list) = self.postings.get_mut(i) {
+                list.retain(|&(doc, _)| doc != id);
+                if list.is_empty() {
+                    self.postings.remove(i);
+                }
+            }
+        }
+        Some(vector)
+    }
+
+    // Exact dot product over the lists the query touches, (score, id) highest first.
+    // Only ids sharing at least one dimension with the query come back.
+    pub fn search(&self, query: &SparseVector, k: usize) -> Vec<(f32, usize)> {
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for (i, q) in query.iter() {
+            if let Some(list) = self.postings.get(&i) {
+                for &(id, v) in list {
+                    *scores.entry(id).or_insert(0.0) += q * v;
+                }
+            }
+        }
+        let mut results: Vec<(f32, usize)> = scores.into_iter().map(|(id, s)| (s, id)).collect();
+        results.sort_unstable_by_key(|&(s, id)| (std::cmp::Reverse(OrderedFloat(s)), id));
+        results.truncate(k);
+        results
+    }
+}
+
+impl HNSW {
+    pub fn insert_with_sparse(&mut self, vec: &[f32], sparse: SparseVector) -> usize {
+        let id = self.insert(vec);
+        self.sparse_index.insert(id, sparse);
+        id
+    }
+
+    // Attach / replace the sparse vector of an existing point, false if the id doesn't exist
+    pub fn set_sparse(&mut self, id: usize, sparse: SparseVector) -> bool {
+        if !self.contains(id) {
+            return false;
+        }
+        self.sparse_index.insert(id, sparse);
+        true
+    }
+
+    pub fn get_sparse(&self, id: usize) -> Option<&SparseVector> {
+        self.sparse_index.get(id)
+    }
+
+    pub fn sparse_search(&self, query: &SparseVector, k: usize) -> Vec<(f32, usize)> {
+        self.sparse_index.search(query, k)
+    }
+
+    // Dense graph search + sparse dot product, fused like hybrid_search.
+    // alpha weights the dense side, returns (fused score, id), higher is better.
+    pub fn sparse_hybrid_search(
+        &self,
+        dense: &[f32],
+        sparse: &SparseVector,
+        k: usize,
+        alpha: f32,
+        fusion: Fusion,
+    ) -> Vec<(f32, usize)> {
+        let depth = self.config.ef_search.max(k);
+        let dense_hits = self.candidates(dense, depth);
+        let sparse_hits = self.sparse_search(sparse, depth);
+        fuse(&dense_hits, &sparse_hits, k, alpha, fusion)
+    }
+}
@@ -0,0 +1,41 @@
+// Per-query tracing, filled in by HNSW::search_with_stats / search_layer_with_stats.
+// Counters are cheap so the plain search path tracks them too and just drops them.
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayerStats {
+    pub layer: usize,
+    // where the beam search on this layer started
+    pub entry_point: usize,
+    // nodes whose distance was computed on this layer
+    pub visited: usize,
+    pub distance_evaluations: usize,
+    // candidates popped and expanded
+    pub hops: usize,
+    // peak size of the candidate (min) heap
+    pub max_candidates: usize,
+    // size of the result (max) heap when the layer finished
+    pub found: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchStats {
+    // top layer first, base layer last
+    pub layers: Vec<LayerStats>,
+    pub distance_evaluations: usize,
+    pub hops: usize,
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    pub(crate) fn push_layer(&mut self, layer: LayerStats) {
+        self.distance_evaluations += layer.distance_evaluations;
+        self.hops += layer.hops;
+        self.layers.push(layer);
+    }
+
+    pub fn visited_per_layer(&self) -> Vec<(usize, usize)> {
+        self.layers.iter().map(|l| (l.layer, l.visited)).collect()
+    }
+}
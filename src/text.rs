@@ -0,0 +1,221 @@
+// BM25 inverted index over the text payload field, plus hybrid (BM25 + vector) search.
+// Lives inside HNSW so it is saved in the same .pho file and keyed by the same external ids.
+// The index is kept in sync by insert_with_payload / set_payload / delete.
+
+use crate::{Payload, HNSW};
+use ordered_float::OrderedFloat;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+pub const DEFAULT_TEXT_FIELD: &str = "text";
+// usual RRF constant from the original paper
+pub const RRF_K: f32 = 60.0;
+
+// lowercase alphanumeric runs, so "ERR-404" -> ["err", "404"] on both sides
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TextIndex {
+    // payload key that gets indexed
+    pub field: String,
+    pub k1: f32,
+    pub b: f32,
+    // term -> [(external id, term frequency)]
+    pub postings: BTreeMap<String, Vec<(usize, u32)>>,
+    // external id -> document length in tokens
+    pub doc_lengths: BTreeMap<usize, u32>,
+    pub total_tokens: u64,
+}
+
+impl Default for TextIndex {
+    fn default() -> Self {
+        TextIndex {
+            field: DEFAULT_TEXT_FIELD.to_string(),
+            k1: 1.2,
+            b: 0.75,
+            postings: BTreeMap::new(),
+            doc_lengths: BTreeMap::new(),
+            total_tokens: 0,
+        }
+    }
+}
+
+impl TextIndex {
+    pub fn len(&self) -> usize {
+        self.doc_lengths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.doc_lengths.is_empty()
+    }
+
+    pub fn add(&mut self, id: usize, text: &str) {
+        let tokens = tokenize(text);
+        let mut tf: BTreeMap<String, u32> = BTreeMap::new();
+        for t in &tokens {
+            *tf.entry(t.clone()).or_insert(0) += 1;
+        }
+        for (term, count) in tf {
+            self.postings.entry(term).or_default().push((id, count));
+        }
+        self.doc_lengths.insert(id, tokens.len() as u32);
+        self.total_tokens += tokens.len() as u64;
+    }
+
+    // `text` must be what was added for this id
+    pub fn remove(&mut self, id: usize, text: &str) {
+        let len = match self.doc_lengths.remove(&id) {
+            Some(len) => len,
+            None => return,
+        };
+        self.total_tokens -= len as u64;
+        for term in tokenize(text) {
+            if let Some(list) = self.postings.get_mut(&term) {
+                list.retain(|&(doc, _)| doc != id);
+                if list.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    // (score, id), best first
+    pub fn search(&self, query: &str, k: usize) -> Vec<(f32, usize)> {
+        let n = self.doc_lengths.len() as f32;
+        if n == 0.0 {
+            return Vec::new();
+        }
+        let avg_len = self.total_tokens as f32 / n;
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for term in terms {
+            let list = match self.postings.get(&term) {
+                Some(list) => list,
+                None => continue,
+            };
+            let df = list.len() as f32;
+            let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+            for &(doc, tf) in list {
+                let tf = tf as f32;
+                let dl = self.doc_lengths[&doc] as f32;
+                let norm = tf + self.k1 * (1.0 - self.b + self.b * dl / avg_len);
+                *scores.entry(doc).or_insert(0.0) += idf * tf * (self.k1 + 1.0) / norm;
+            }
+        }
+
+        let mut results: Vec<(f32, usize)> = scores.into_iter().map(|(id, s)| (s, id)).collect();
+        results.sort_unstable_by_key(|&(s, id)| (std::cmp::Reverse(OrderedFloat(s)), id));
+        results.truncate(k);
+        results
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Fusion {
+    // sum of weight / (RRF_K + rank), only ranks matter
+    #[default]
+    Rrf,
+    // min-max normalized scores from both sides, mixed linearly
+    Weighted,
+}
+
+impl Fusion {
+    pub fn parse(name: &str) -> Result<Fusion, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "rrf" => Ok(Fusion::Rrf),
+            "weighted" => Ok(Fusion::Weighted),
+            other => Err(format!("Unknown fusion: {}", other)),
+        }
+    }
+}
+
+// min-max to [0, 1]; all equal -> 1
+fn normalize(values: &[f32]) -> Vec<f32> {
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    values
+        .iter()
+        .map(|v| if max > min { (v - min) / (max - min) } else { 1.0 })
+        .collect()
+}
+
+// Merges vector hits (distance, id) with keyword-style hits (score, id, higher is better).
+// alpha weights the vector side. Returns (fused score, id), best first.
+pub(crate) fn fuse(
+    vector_hits: &[(f32, usize)],
+    other_hits: &[(f32, usize)],
+    k: usize,
+    alpha: f32,
+    fusion: Fusion,
+) -> Vec<(f32, usize)> {
+    let mut fused: HashMap<usize, f32> = HashMap::new();
+    match fusion {
+        Fusion::Rrf => {
+            for (rank, &(_, id)) in vector_hits.iter().enumerate() {
+                *fused.entry(id).or_insert(0.0) += alpha / (RRF_K + rank as f32 + 1.0);
+            }
+            for (rank, &(_, id)) in other_hits.iter().enumerate() {
+                *fused.entry(id).or_insert(0.0) += (1.0 - alpha) / (RRF_K + rank as f32 + 1.0);
+            }
+        }
+        Fusion::Weighted => {
+            // distances: smaller is better, so normalize the negated values
+            let vector_scores = normalize(&vector_hits.iter().map(|(d, _)| -d).collect::<Vec<_>>());
+            for (&(_, id), s) in vector_hits.iter().zip(vector_scores) {
+                *fused.entry(id).or_insert(0.0) += alpha * s;
+            }
+            let other_scores = normalize(&other_hits.iter().map(|(s, _)| *s).collect::<Vec<_>>());
+            for (&(_, id), s) in other_hits.iter().zip(other_scores) {
+                *fused.entry(id).or_insert(0.0) += (1.0 - alpha) * s;
+            }
+        }
+    }
+
+    let mut results: Vec<(f32, usize)> = fused.into_iter().map(|(id, s)| (s, id)).collect();
+    results.sort_unstable_by_key(|&(s, id)| (std::cmp::Reverse(OrderedFloat(s)), id));
+    results.truncate(k);
+    results
+}
+
+impl HNSW {
+    pub(crate) fn index_text(&mut self, id: usize, payload: &Payload) {
+        if let Some(text) = payload.get(&self.text_index.field).and_then(|v| v.as_str()) {
+            self.text_index.add(id, text);
+        }
+    }
+
+    pub(crate) fn unindex_text(&mut self, id: usize) {
+        let text = self
+            .payloads
+            .get(&id)
+            .and_then(|p| p.get(&self.text_index.field))
+            .and_then(|v| v.as_str());
+        if let Some(text) = text {
+            self.text_index.remove(id, text);
+        }
+    }
+
+    // BM25 only, (score, id) best first
+    pub fn text_search(&self, text: &str, k: usize) -> Vec<(f32, usize)> {
+        self.text_index.search(text, k)
+    }
+
+    // Fuses BM25 over the text payloads with graph search. alpha weights the vector side
+    // (1.0 = vector only, 0.0 = text only). Returns (fused score, id), higher is better.
+    pub fn hybrid_search(&self, text: &str, vector: &[f32], k: usize, alpha: f32, fusion: Fusion) -> Vec<(f32, usize)> {
+        let depth = self.config.ef_search.max(k);
+        let text_hits = self.text_search(text, depth);
+        let vector_hits = self.candidates(vector, depth);
+
+        fuse(&vector_hits, &text_hits, k, alpha, fusion)
+    }
+}
@@ -0,0 +1,334 @@
+// Tiered storage: the graph stays in RAM, the vectors move to a flat file (a 32 byte header,
+// then dim native endian f32 per row) that is mapped read only, so the OS pages rows in on
+// demand and can evict them again under memory pressure.
+//
+// Header: VEC_MAGIC, dim, rows, generation (u64 LE each). The generation is random and gets
+// replaced whenever rows are rewritten (compact, reorder) or dropped, TierConfig keeps the
+// one the index was built against so attach() refuses a file that no longer matches.
+//
+// Rows [0, mapped_rows) come from the file, the rest from VectorStore::data. New vectors
+// collect in data until it passes the memory budget, then get appended to the file.
+// The budget also picks the read-ahead policy: a file that fits in it is prefetched as a
+// whole, a bigger one is marked random access and only the rows search is about to touch
+// get a WillNeed hint (prefetch).
+
+use crate::{VectorStore, HNSW};
+use memmap2::Mmap;
+use rkyv::{Archive, Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+
+const VEC_MAGIC: &[u8; 8] = b"PHOTVECS";
+const HEADER_BYTES: usize = 32;
+
+#[derive(Archive, Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TierConfig {
+    // the vector file, created by set_tier
+    pub path: String,
+    // bytes of vectors kept in RAM before they are appended to the file
+    pub memory_budget: usize,
+    // WillNeed hints for the neighbors of the next node to expand
+    pub prefetch: bool,
+    // generation of the vector file these rows belong to, set by set_tier
+    pub generation: u64,
+}
+
+impl TierConfig {
+    pub fn new(path: impl Into<String>) -> Self {
+        TierConfig {
+            path: path.into(),
+            memory_budget: 64 << 20,
+            prefetch: true,
+            generation: 0,
+        }
+    }
+
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
+
+    pub fn prefetch(mut self, prefetch: bool) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+}
+
+// The live mapping, never serialized: VectorStore::attach() recreates it after a load
+#[derive(Default)]
+pub struct Mapping(Option<Mmap>);
+
+impl std::fmt::Debug for Mapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mapping({} bytes)", self.0.as_ref().map_or(0, |m| m.len()))
+    }
+}
+
+// the mapped rows are covered by mapped_rows + the tier path
+impl PartialEq for Mapping {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+fn io_err(path: &str, e: std::io::Error) -> String {
+    format!("{}: {}", path, e)
+}
+
+fn write_header(out: &mut impl Write, dim: usize, rows: usize, generation: u64) -> std::io::Result<()> {
+    out.write_all(VEC_MAGIC)?;
+    for x in [dim as u64, rows as u64, generation] {
+        out.write_all(&x.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+// (dim, rows, generation)
+fn read_header(path: &str) -> Result<(usize, usize, u64), String> {
+    let mut header = [0u8; HEADER_BYTES];
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .map_err(|e| io_err(path, e))?;
+    if &header[..8] != VEC_MAGIC {
+        return Err(format!("{} is not a photon vector file", path));
+    }
+    let field = |i: usize| u64::from_le_bytes(header[8 * i..8 * (i + 1)].try_into().unwrap());
+    Ok((field(1) as usize, field(2) as usize, field(3)))
+}
+
+impl VectorStore {
+    pub fn is_tiered(&self) -> bool {
+        self.tier.is_some()
+    }
+
+    // bytes of vectors held in RAM, the mapped ones not counted
+    pub fn ram_bytes(&self) -> usize {
+        self.data.len() * std::mem::size_of::<f32>()
+    }
+
+    fn row_bytes(&self) -> usize {
+        self.dim * std::mem::size_of::<f32>()
+    }
+
+    pub(crate) fn mapped_row(&self, row: usize) -> &[f32] {
+        let mmap = self.mapping.0.as_ref().expect("Vector file not attached, call attach() after loading");
+        let start = HEADER_BYTES + row * self.row_bytes();
+        let bytes = &mmap[start..start + self.row_bytes()];
+        // the mapping is page aligned, the header and rows are whole f32s
+        debug_assert_eq!(bytes.as_ptr() as usize % std::mem::align_of::<f32>(), 0);
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const f32, self.dim) }
+    }
+
+    // Moves every row into tier.path (overwritten) and maps it
+    pub fn set_tier(&mut self, mut tier: TierConfig) -> Result<(), String> {
+        if self.is_tiered() {
+            return Err("Vectors are already tiered, call load_into_memory first".to_string());
+        }
+        let rows: Vec<usize> = (0..self.len()).collect();
+        tier.generation = self.write_rows(&tier.path, &rows)?;
+        self.mapped_rows = rows.len();
+        self.data = Vec::new();
+        self.tier = Some(tier);
+        self.remap()
+    }
+
+    // Writes header + rows to path (overwritten) under a fresh generation, which it returns
+    fn write_rows(&self, path: &str, rows: &[usize]) -> Result<u64, String> {
+        let generation = rand::random::<u64>();
+        let mut out = BufWriter::new(File::create(path).map_err(|e| io_err(path, e))?);
+        write_header(&mut out, self.dim, rows.len(), generation).map_err(|e| io_err(path, e))?;
+        for &row in rows {
+            for x in self.get(row) {
+                out.write_all(&x.to_ne_bytes()).map_err(|e| io_err(path, e))?;
+            }
+        }
+        out.flush().map_err(|e| io_err(path, e))?;
+        Ok(generation)
+    }
+
+    // Back to plain RAM storage, the file is left alone
+    pub fn load_into_memory(&mut self) {
+        let mut data = Vec::with_capacity(self.len() * self.dim);
+        for row in 0..self.len() {
+            data.extend_from_slice(self.get(row));
+        }
+        self.data = data;
+        self.mapped_rows = 0;
+        self.mapping = Mapping::default();
+        self.tier = None;
+    }
+
+    // Maps the vector file of a store that was just deserialized, no-op when not tiered.
+    // Rows past mapped_rows are fine (spilled after the last save), anything else that
+    // doesn't match means the file was rewritten since and the rows would be wrong.
+    pub fn attach(&mut self) -> Result<(), String> {
+        let Some(tier) = &self.tier else {
+            return Ok(());
+        };
+        let (dim, rows, generation) = read_header(&tier.path)?;
+        if dim != self.dim {
+            return Err(format!("{} holds {}-d vectors, the index expects {}", tier.path, dim, self.dim));
+        }
+        if generation != tier.generation {
+            return Err(format!(
+                "{} was rewritten (compact, reorder or a later spill) after this index was saved",
+                tier.path
+            ));
+        }
+        let len = std::fs::metadata(&tier.path).map_err(|e| io_err(&tier.path, e))?.len() as usize;
+        let on_disk = rows.min(len.saturating_sub(HEADER_BYTES) / self.row_bytes());
+        if on_disk < self.mapped_rows {
+            return Err(format!("{} holds {} rows, the index expects {}", tier.path, on_disk, self.mapped_rows));
+        }
+        self.remap()
+    }
+
+    // Appends the in-RAM rows to the file
+    pub fn spill(&mut self) -> Result<(), String> {
+        let Some(tier) = &self.tier else {
+            return Ok(());
+        };
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        let path = tier.path.clone();
+        let (_, file_rows, mut generation) = read_header(&path)?;
+        // rows past mapped_rows belong to a state that never got saved here, overwriting them
+        // means any .pho that does reference them must not load
+        if file_rows > self.mapped_rows {
+            generation = rand::random::<u64>();
+        }
+        let rows = self.len();
+        let file = OpenOptions::new().write(true).open(&path).map_err(|e| io_err(&path, e))?;
+        file.set_len((HEADER_BYTES + self.mapped_rows * self.row_bytes()) as u64)
+            .map_err(|e| io_err(&path, e))?;
+        let mut out = BufWriter::new(file);
+        out.seek(SeekFrom::End(0)).map_err(|e| io_err(&path, e))?;
+        for x in &self.data {
+            out.write_all(&x.to_ne_bytes()).map_err(|e| io_err(&path, e))?;
+        }
+        // rows count last, a failed append leaves a header that still matches the file
+        out.seek(SeekFrom::Start(0)).map_err(|e| io_err(&path, e))?;
+        write_header(&mut out, self.dim, rows, generation).map_err(|e| io_err(&path, e))?;
+        out.flush().map_err(|e| io_err(&path, e))?;
+        self.mapped_rows = rows;
+        self.data = Vec::new();
+        if let Some(tier) = &mut self.tier {
+            tier.generation = generation;
+        }
+        self.remap()
+    }
+
+    // Spills once the RAM tail is over the memory budget, no-op otherwise
+    pub fn enforce_budget(&mut self) -> Result<(), String> {
+        match &self.tier {
+            Some(tier) if self.ram_bytes() > tier.memory_budget => self.spill(),
+            _ => Ok(()),
+        }
+    }
+
+    fn remap(&mut self) -> Result<(), String> {
+        let tier = self.tier.as_ref().expect("remap on a store without a tier");
+        self.mapping = Mapping::default();
+        if self.mapped_rows == 0 {
+            return Ok(());
+        }
+        let file = File::open(&tier.path).map_err(|e| io_err(&tier.path, e))?;
+        // the file is only ever appended to, never rewritten while mapped
+        let len = HEADER_BYTES + self.mapped_rows * self.row_bytes();
+        let mmap = unsafe { memmap2::MmapOptions::new().len(len).map(&file) }.map_err(|e| io_err(&tier.path, e))?;
+        #[cfg(unix)]
+        {
+            let advice = if self.fits_budget(tier) {
+                memmap2::Advice::WillNeed
+            } else {
+                memmap2::Advice::Random
+            };
+            mmap.advise(advice).map_err(|e| io_err(&tier.path, e))?;
+        }
+        self.mapping = Mapping(Some(mmap));
+        Ok(())
+    }
+
+    // the whole file was prefetched by remap, per-row hints would only cost syscalls
+    fn fits_budget(&self, tier: &TierConfig) -> bool {
+        self.mapped_rows * self.row_bytes() <= tier.memory_budget
+    }
+
+    // Read-ahead hint for rows that are about to be read, only for mapped rows. Rows are
+    // coalesced into runs of touching pages, one madvise per run.
+    pub fn prefetch(&self, rows: impl IntoIterator<Item = usize>) {
+        #[cfg(unix)]
+        if let (Some(tier), Some(mmap)) = (&self.tier, &self.mapping.0) {
+            if !tier.prefetch || self.fits_budget(tier) {
+                return;
+            }
+            const PAGE: usize = 4096;
+            let mut pages: Vec<usize> = rows
+                .into_iter()
+                .filter(|&row| row < self.mapped_rows)
+                .flat_map(|row| {
+                    let start = HEADER_BYTES + row * self.row_bytes();
+                    start / PAGE..=(start + self.row_bytes() - 1) / PAGE
+                })
+                .collect();
+            pages.sort_unstable();
+            pages.dedup();
+            let mut i = 0;
+            while i < pages.len() {
+                let mut j = i + 1;
+                while j < pages.len() && pages[j] == pages[j - 1] + 1 {
+                    j += 1;
+                }
+                let start = pages[i] * PAGE;
+                let end = ((pages[j - 1] + 1) * PAGE).min(mmap.len());
+                // only a hint, a failure just means no read-ahead
+                let _ = mmap.advise_range(memmap2::Advice::WillNeed, start, end - start);
+                i = j;
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = rows;
+    }
+
+    // Keeps only `rows`, in that order. A tiered store writes them to a new file under a
+    // new generation that replaces the old one, a .pho saved before that won't attach to it.
+    // On error the store is left as it was.
+    pub(crate) fn keep_rows(&mut self, rows: &[usize]) -> Result<(), String> {
+        let Some(mut tier) = self.tier.clone() else {
+            let mut data = Vec::with_capacity(rows.len() * self.dim);
+            for &row in rows {
+                data.extend_from_slice(self.get(row));
+            }
+            self.data = data;
+            return Ok(());
+        };
+        let tmp = format!("{}.tmp", tier.path);
+        tier.generation = self.write_rows(&tmp, rows)?;
+        std::fs::rename(&tmp, &tier.path).map_err(|e| io_err(&tier.path, e))?;
+        self.mapped_rows = rows.len();
+        self.data = Vec::new();
+        self.tier = Some(tier);
+        self.remap()
+    }
+}
+
+impl HNSW {
+    // Vectors to a mapped file at tier.path, the graph stays in RAM. Saving the index
+    // afterwards stores the path, loading maps the same file again.
+    pub fn set_tier(&mut self, tier: TierConfig) -> Result<(), String> {
+        self.vectors.set_tier(tier)
+    }
+
+    // Flushes the in-RAM tail so the file holds every row, e.g. before copying it around
+    pub fn spill_vectors(&mut self) -> Result<(), String> {
+        self.vectors.spill()
+    }
+
+    // insert() never touches the file, call this every so often during a bulk load to keep
+    // the RAM tail under the memory budget
+    pub fn enforce_memory_budget(&mut self) -> Result<(), String> {
+        self.vectors.enforce_budget()
+    }
+}
+
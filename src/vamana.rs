@@ -0,0 +1,286 @@
+// Vamana (the DiskANN graph): one flat layer instead of HNSW's hierarchy, built so that
+// greedy search from a single medoid converges in few hops. That's what makes it usable
+// from SSD, every hop is one block read. See disk.rs for the on-disk side.
+//
+// Build = two passes over the points in random order (alpha = 1, then config.alpha):
+// greedy search for the point, robust-prune the visited set down to r neighbors,
+// add the reverse edges and re-prune any neighbor that overflows.
+
+use crate::{Metric, VectorStore};
+use ordered_float::OrderedFloat;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rayon::prelude::*;
+use std::collections::HashSet;
+
+// points searched in parallel against the same snapshot of the graph before their
+// edges get applied, bigger = faster build, slightly worse graph
+const BUILD_BATCH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VamanaConfig {
+    // max out-degree, also the neighbor slots reserved per block on disk
+    pub r: usize,
+    // candidate list size while building
+    pub l_build: usize,
+    // default candidate list size for queries
+    pub l_search: usize,
+    // > 1 keeps some longer edges, fewer hops per query
+    pub alpha: f32,
+    // PQ bytes per vector kept in RAM by the disk index, 0 = dim / 4 (or dim)
+    pub pq_m: usize,
+    pub seed: Option<u64>,
+}
+
+impl Default for VamanaConfig {
+    fn default() -> Self {
+        VamanaConfig {
+            r: 64,
+            l_build: 100,
+            l_search: 64,
+            alpha: 1.2,
+            pq_m: 0,
+            seed: None,
+        }
+    }
+}
+
+impl VamanaConfig {
+    pub fn new() -> Self {
+        VamanaConfig::default()
+    }
+
+    pub fn r(mut self, r: usize) -> Self {
+        self.r = r;
+        self
+    }
+
+    pub fn l_build(mut self, l_build: usize) -> Self {
+        self.l_build = l_build;
+        self
+    }
+
+    pub fn l_search(mut self, l_search: usize) -> Self {
+        self.l_search = l_search;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: f32) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn pq(mut self, pq_m: usize) -> Self {
+        self.pq_m = pq_m;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn validate(&self, dim: usize) -> Result<(), String> {
+        if self.r < 2 {
+            return Err(format!("r must be at least 2, got {}", self.r));
+        }
+        if self.l_build < self.r {
+            return Err(format!("l_build ({}) must be >= r ({})", self.l_build, self.r));
+        }
+        if self.l_search == 0 {
+            return Err("l_search must be greater than 0".to_string());
+        }
+        if !self.alpha.is_finite() || self.alpha < 1.0 {
+            return Err(format!("alpha must be >= 1.0, got {}", self.alpha));
+        }
+        if self.pq_m > 0 && !dim.is_multiple_of(self.pq_m) {
+            return Err(format!("dim ({}) must be a multiple of pq m ({})", dim, self.pq_m));
+        }
+        Ok(())
+    }
+
+    // PQ sub-vectors actually used: 4 floats per byte when dim allows it, else 1
+    pub fn pq_m_for(&self, dim: usize) -> usize {
+        match self.pq_m {
+            0 if dim.is_multiple_of(4) => dim / 4,
+            0 => dim,
+            m => m,
+        }
+    }
+
+    // Builds the graph over every row of vectors, node ids are the row numbers
+    pub fn build(self, vectors: VectorStore) -> Result<VamanaIndex, String> {
+        self.validate(vectors.dim)?;
+        let mut index = VamanaIndex {
+            neighbors: vec![Vec::new(); vectors.len()],
+            medoid: medoid(&vectors),
+            vectors,
+            config: self,
+        };
+        index.build_graph();
+        Ok(index)
+    }
+}
+
+// the point closest to the mean, every search starts there
+fn medoid(vectors: &VectorStore) -> usize {
+    if vectors.is_empty() {
+        return 0;
+    }
+    let mut mean = vec![0.0f32; vectors.dim];
+    for row in 0..vectors.len() {
+        for (m, x) in mean.iter_mut().zip(vectors.get(row)) {
+            *m += x;
+        }
+    }
+    let n = vectors.len() as f32;
+    mean.iter_mut().for_each(|m| *m /= n);
+    (0..vectors.len())
+        .min_by_key(|&i| OrderedFloat(vectors.squared_distance_to_query(i, &mean)))
+        .unwrap()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct VamanaIndex {
+    pub config: VamanaConfig,
+    pub vectors: VectorStore,
+    // node -> out-neighbors, at most config.r each
+    pub neighbors: Vec<Vec<usize>>,
+    pub medoid: usize,
+}
+
+impl VamanaIndex {
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    fn rng(&self) -> StdRng {
+        match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        }
+    }
+
+    fn build_graph(&mut self) {
+        let n = self.len();
+        if n < 2 {
+            return;
+        }
+        // random r-regular start so the first searches have somewhere to go
+        let mut rng = self.rng();
+        let degree = self.config.r.min(n - 1);
+        for p in 0..n {
+            let mut picked: Vec<usize> = rand::seq::index::sample(&mut rng, n - 1, degree).into_iter().collect();
+            // sample over n - 1 values, shift to skip p itself
+            picked.iter_mut().filter(|q| **q >= p).for_each(|q| *q += 1);
+            self.neighbors[p] = picked;
+        }
+
+        let mut order: Vec<usize> = (0..n).collect();
+        order.shuffle(&mut rng);
+        for alpha in [1.0, self.config.alpha] {
+            for batch in order.chunks(BUILD_BATCH) {
+                let visited: Vec<Vec<usize>> = batch
+                    .par_iter()
+                    .map(|&p| self.greedy_search(self.vectors.get(p), self.config.l_build).1)
+                    .collect();
+                for (&p, visited) in batch.iter().zip(visited) {
+                    let mut candidates = visited;
+                    candidates.extend_from_slice(&self.neighbors[p]);
+                    self.neighbors[p] = self.robust_prune(p, candidates, alpha);
+                    for j in self.neighbors[p].clone() {
+                        if self.neighbors[j].contains(&p) {
+                            continue;
+                        }
+                        if self.neighbors[j].len() < self.config.r {
+                            self.neighbors[j].push(p);
+                        } else {
+                            let mut candidates = self.neighbors[j].clone();
+                            candidates.push(p);
+                            self.neighbors[j] = self.robust_prune(j, candidates, alpha);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Keeps the closest candidate, drops everything it "covers" (alpha * d(kept, c) <= d(p, c)),
+    // repeats until r neighbors or no candidates left.
+    // Euclidean distances are squared here, so the factor is squared too. The alpha scaling
+    // only makes sense for distances >= 0, inner product prunes with alpha = 1
+    fn robust_prune(&self, p: usize, candidates: Vec<usize>, alpha: f32) -> Vec<usize> {
+        let alpha = match self.vectors.metric {
+            Metric::Euclidean => alpha * alpha,
+            Metric::Cosine => alpha,
+            Metric::InnerProduct => 1.0,
+        };
+        let mut candidates: Vec<(OrderedFloat<f32>, usize)> = candidates
+            .into_iter()
+            .filter(|&c| c != p)
+            .collect::<HashSet<usize>>()
+            .into_iter()
+            .map(|c| (OrderedFloat(self.vectors.distance(p, c)), c))
+            .collect();
+        candidates.sort_unstable();
+
+        let mut kept = Vec::with_capacity(self.config.r);
+        while !candidates.is_empty() && kept.len() < self.config.r {
+            let (_, best) = candidates.remove(0);
+            kept.push(best);
+            candidates.retain(|&(OrderedFloat(d), c)| alpha * self.vectors.distance(best, c) > d);
+        }
+        kept
+    }
+
+    // Best-first search from the medoid with an l sized candidate list.
+    // Returns the list (distance, node) closest first and every node expanded on the way.
+    pub(crate) fn greedy_search(&self, query: &[f32], l: usize) -> (Vec<(f32, usize)>, Vec<usize>) {
+        if self.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+        let mut seen = HashSet::from([self.medoid]);
+        // (distance, node, expanded), sorted
+        let mut list = vec![(OrderedFloat(self.vectors.distance_to_query(self.medoid, query)), self.medoid, false)];
+        let mut expanded = Vec::new();
+        while let Some(i) = list.iter().position(|&(_, _, done)| !done) {
+            list[i].2 = true;
+            let node = list[i].1;
+            expanded.push(node);
+            for &nb in &self.neighbors[node] {
+                if !seen.insert(nb) {
+                    continue;
+                }
+                let d = OrderedFloat(self.vectors.distance_to_query(nb, query));
+                if list.len() < l || d < list[list.len() - 1].0 {
+                    let at = list.partition_point(|&(x, _, _)| x <= d);
+                    list.insert(at, (d, nb, false));
+                    list.truncate(l);
+                }
+            }
+        }
+        let found = list.into_iter().map(|(OrderedFloat(d), node, _)| (d, node)).collect();
+        (found, expanded)
+    }
+
+    // In-RAM search on the full vectors, (distance, node) closest first
+    pub fn search(&self, query: &[f32], k: usize, l_search: Option<usize>) -> Vec<(f32, usize)> {
+        let l = l_search.unwrap_or(self.config.l_search).max(k);
+        let mut found = self.greedy_search(query, l).0;
+        found.truncate(k);
+        found
+    }
+
+    pub fn brute_force_search(&self, query: &[f32], k: usize) -> Vec<(f32, usize)> {
+        let mut all: Vec<(OrderedFloat<f32>, usize)> = (0..self.len())
+            .map(|i| (OrderedFloat(self.vectors.distance_to_query(i, query)), i))
+            .collect();
+        all.sort_unstable();
+        all.into_iter().take(k).map(|(OrderedFloat(d), i)| (d, i)).collect()
+    }
+}
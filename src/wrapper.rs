@@ -1,77 +1,84 @@
-use crate::HNSW;
-use rkyv::{from_bytes, to_bytes, rancor::Error};
+use crate::{AnyIndex, GraphDiagnostics, Index, HNSW};
 use std::fs::{self};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+const WRAPPER_EF_SEARCH: usize = 100;
+
+#[allow(non_camel_case_types)]
 pub struct db {
     hnsw: HNSW,
     path: PathBuf,
 }
 
 impl db {
+    // panics on a file that can't be loaded, open() returns the error instead
     pub fn new(path: String, _dim: usize, _max_elements: usize) -> Self {
+        Self::open(path, _dim, _max_elements).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn open(path: String, _dim: usize, _max_elements: usize) -> Result<Self, String> {
         let path_buf = PathBuf::from(path);
         if path_buf.exists() {
             Self::load_from_path(path_buf)
         } else {
              let hnsw = HNSW::new(_max_elements, _dim);
-             db { hnsw, path: path_buf }
+             Ok(db { hnsw, path: path_buf })
         }
     }
 
-    fn load_from_path(path: PathBuf) -> Self {
-        let bytes = fs::read(&path).expect("Failed to read database file");
-        let hnsw: HNSW = from_bytes::<HNSW, Error>(&bytes).expect("Failed to deserialize database");
-        db { hnsw, path }
+    // same format and checks as the CLI and PhotonDB::load, a broken graph fails here and
+    // not in a search. Old headerless files still load.
+    fn load_from_path(path: PathBuf) -> Result<Self, String> {
+        let bytes = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        match AnyIndex::from_bytes(&bytes).map_err(|e| format!("Failed to load {}: {}", path.display(), e))? {
+            AnyIndex::Hnsw(hnsw) => Ok(db { hnsw, path }),
+            other => Err(format!("{} holds a {} index, db only opens hnsw", path.display(), other.kind().name())),
+        }
     }
 
     pub fn add(&mut self, vec: Vec<f32>) {
         if vec.len() != self.hnsw.vectors.dim {
             panic!("Vector dimension mismatch");
         }
-        
     
-        let id = self.hnsw.vectors.insert(&vec);
-
-        
-        let m = self.hnsw.m;
-        let m_max = m; 
-        let ef_construction = self.hnsw.ef_construction;
-        let m_l = 1.0 / (m as f32).ln();
-
-        self.hnsw.insert(id, m, m_max, ef_construction, m_l);
+        self.hnsw.insert(&vec);
     }
 
+    // the wrapper always searched with ef 100, not the config default
     pub fn search(&self, query: Vec<f32>, k: usize) -> Vec<(f32, usize)> {
-        
-        let ef_search = 100; 
-        self.hnsw.search(&query, k, ef_search)
+        self.hnsw.search(&query, k, Some(WRAPPER_EF_SEARCH))
     }
 
-    // pub fn delete(&mut self) {
-    
-    // }
+    pub fn delete(&mut self, id: usize) -> bool {
+        self.hnsw.delete(id)
+    }
 
-    // pub fn get(&self, _id: usize) {
-        
-    // }
+    pub fn get(&self, id: usize) -> Option<Vec<f32>> {
+        self.hnsw.get_vector(id).map(|v| v.to_vec())
+    }
 
     pub fn save(&self) {
-        let bytes = to_bytes::<Error>(&self.hnsw).expect("Failed to serialize database");
-        fs::write(&self.path, bytes).expect("Failed to write database file");
+        // with the index header, like every other writer
+        Index::save(&self.hnsw, &self.path).expect("Failed to write database file");
     }
 
     // pub fn load() {
         
     // }
 
-    // pub fn export_vectors(&self) {
-         
-    // }
+    // .npy or .fvecs by extension, returns the ids in row order
+    pub fn export_vectors(&self, path: &str) -> Result<Vec<usize>, String> {
+        self.hnsw.export(Path::new(path))
+    }
 
-    // pub fn search_by_id(&self) {
-         
-    // }
+    // MMR re-ranked search, fetches 4 * k candidates
+    pub fn search_mmr(&self, query: Vec<f32>, k: usize, lambda: f32) -> Result<Vec<(f32, usize)>, String> {
+        self.hnsw.search_mmr(&query, k, k * 4, lambda)
+    }
+
+    pub fn search_by_id(&self, id: usize, k: usize) -> Option<Vec<(f32, usize)>> {
+        self.hnsw.search_by_id(id, k, None, &[])
+    }
     
     pub fn getneighbors(&self, layer: usize, node: usize) -> Vec<usize> {
         // GraphLayers fields are public in lib.rs so this is possible
@@ -94,24 +101,37 @@ impl db {
         }
     }
 
-    // pub fn update_vector(&mut self) {
-        
-    // }
+    pub fn update_vector(&mut self, id: usize, vec: Vec<f32>) -> bool {
+        if vec.len() != self.hnsw.vectors.dim {
+            panic!("Vector dimension mismatch");
+        }
+        self.hnsw.update(id, &vec)
+    }
+
+    // Removes deleted / superseded points for good and rewrites the file
+    pub fn compact(&mut self) -> Result<usize, String> {
+        let removed = self.hnsw.compact()?;
+        self.save();
+        Ok(removed)
+    }
 
     // pub fn merge_vector(&self) {
         
     // }
 
     pub fn count(&self) -> usize {
-        self.hnsw.layers.base_layer.len()
+        self.hnsw.len()
+    }
+
+    pub fn diagnostics(&self) -> GraphDiagnostics {
+        self.hnsw.diagnostics()
     }
 
     pub fn stats(&self) {
         println!("HNSW Stats:");
         println!("  Max Level: {}", self.hnsw.max_level);
-        println!("  Current Max Layer: {}", self.hnsw.layers.upper_layers.len());
-        println!("  Total Vectors (Base Layer Nodes): {}", self.hnsw.layers.base_layer.len());
-        println!("  Entry Point: {:?}", self.hnsw.entry_point);
+        println!("  M: {}, ef_construction: {}, ef_search: {}", self.hnsw.config.m, self.hnsw.config.ef_construction, self.hnsw.config.ef_search);
+        println!("{}", self.hnsw.diagnostics());
     }
 }
 
@@ -144,7 +164,7 @@ mod tests {
         let query = vec![1.0, 1.0, 1.0, 1.0];
         let results = database.search(query, 2);
         
-        assert!(results.len() > 0);
+        assert!(!results.is_empty());
         
         assert_eq!(results[0].0, 0.0);
 
@@ -152,6 +172,20 @@ mod tests {
         
         let loaded_db = db::new(path.to_string(), dim, max_elements);
         assert_eq!(loaded_db.count(), 3);
+        // same file format as the CLI / PhotonDB, so `photon compact` output opens again
+        let any = AnyIndex::from_bytes(&fs::read(path).unwrap()).unwrap();
+        assert_eq!(any.kind(), crate::IndexKind::Hnsw);
+        fs::write(path, any.to_bytes().unwrap()).unwrap();
+        assert_eq!(db::open(path.to_string(), dim, max_elements).unwrap().count(), 3);
+        let flat: AnyIndex = crate::FlatIndex::new(dim, crate::Metric::Euclidean).into();
+        fs::write(path, flat.to_bytes().unwrap()).unwrap();
+        assert!(db::open(path.to_string(), dim, max_elements).is_err());
+
+        // an edge to a node that doesn't exist is reported on open
+        let mut broken = loaded_db;
+        broken.hnsw.layers.base_layer[0].push(99);
+        broken.save();
+        assert!(db::open(path.to_string(), dim, max_elements).is_err());
         
         fs::remove_file(path).unwrap();
     }
@@ -1,7 +1,9 @@
 use photon_db::HNSW;
+use photon_db::{Fusion, HnswConfig, Metric, Payload, PayloadValue, RecommendStrategy};
 use photon_db::persistence::PhotonDB;
 use photon_db::VectorStore;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::fs;
 // use std::path::PathBuf;
 
@@ -10,6 +12,12 @@ fn generate_random_vector(dim: usize) -> Vec<f32> {
     (0..dim).map(|_| rng.random::<f32>()).collect()
 }
 
+// same data on every run, for tests whose assertions depend on the graph that gets built
+fn seeded_vectors(n: usize, dim: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..n).map(|_| (0..dim).map(|_| rng.random::<f32>()).collect()).collect()
+}
+
 #[test]
 fn test_vector_store() {
     let dim = 4;
@@ -37,15 +45,11 @@ fn test_hnsw_basic() {
     let dim = 128;
     let max_elements = 100;
     let mut hnsw = HNSW::new(max_elements, dim);
-    let m = 16;
-    let ef_construction = 64;
-    let m_l = 1.0 / (m as f32).ln();
 
     let v1 = generate_random_vector(dim);
-    let id = hnsw.vectors.insert(&v1);
-    hnsw.insert(id, m, m, ef_construction, m_l);
+    let id = hnsw.insert(&v1);
 
-    let results = hnsw.search(&v1, 1, 64);
+    let results = hnsw.search(&v1, 1, Some(64));
     assert_eq!(results.len(), 1);
     assert_eq!(results[0].1, id);
     assert!(results[0].0 < 1e-6);
@@ -55,18 +59,19 @@ fn test_hnsw_basic() {
 fn test_hnsw_recall() {
     let dim = 32;
     let n = 200;
-    let mut hnsw = HNSW::new(n, dim);
-    let m = 16;
-    let ef_construction = 64;
-    let m_l = 1.0 / (m as f32).ln();
+    let mut hnsw = HnswConfig::new(dim)
+        .max_elements(n)
+        .m(16)
+        .ef_construction(64)
+        .build()
+        .unwrap();
 
     let _rng = rand::rng();
     let mut vectors = Vec::new();
 
     for _ in 0..n {
         let v = generate_random_vector(dim);
-        let id = hnsw.vectors.insert(&v);
-        hnsw.insert(id, m, m, ef_construction, m_l);
+        hnsw.insert(&v);
         vectors.push(v);
     }
 
@@ -78,7 +83,7 @@ fn test_hnsw_recall() {
         let query = generate_random_vector(dim);
         
         let bf_results = hnsw.brute_force_search(&query, k);
-        let hnsw_results = hnsw.search(&query, k, 100);
+        let hnsw_results = hnsw.search(&query, k, Some(100));
 
         let bf_ids: Vec<usize> = bf_results.iter().map(|(_, id)| *id).collect();
         let hnsw_ids: Vec<usize> = hnsw_results.iter().map(|(_, id)| *id).collect();
@@ -126,11 +131,1263 @@ fn test_persistence() {
     // Load
     let loaded_db = PhotonDB::load(db_path.clone(), dim).unwrap();
     
-    assert_eq!(loaded_db.hnsw.vectors.data.len(), 8); // 2 vectors * 4 dim
+    assert_eq!(loaded_db.hnsw().unwrap().vectors.data.len(), 8); // 2 vectors * 4 dim
     
-    let results = loaded_db.hnsw.search(&v1, 1, 10);
+    let results = loaded_db.hnsw().unwrap().search(&v1, 1, Some(10));
     assert_eq!(results.len(), 1);
+    assert_eq!(loaded_db.hnsw().unwrap().config, db.hnsw().unwrap().config);
     
     // Cleanup
     fs::remove_dir_all(temp_dir).unwrap();
 }
+
+#[test]
+fn test_config_builder() {
+    assert!(HnswConfig::new(0).build().is_err());
+    assert!(HnswConfig::new(8).m(1).build().is_err());
+    assert!(HnswConfig::new(8).m(16).m_max(8).build().is_err());
+    assert!(HnswConfig::new(8).ef_search(0).build().is_err());
+
+    let dim = 8;
+    let mut hnsw = HnswConfig::new(dim)
+        .metric(Metric::Cosine)
+        .m(8)
+        .ef_construction(32)
+        .ef_search(16)
+        .build()
+        .unwrap();
+    assert_eq!(hnsw.config.m_max0, 16);
+    assert!((hnsw.config.m_l - 1.0 / (8.0f32).ln()).abs() < 1e-6);
+
+    for _ in 0..50 {
+        hnsw.insert(&generate_random_vector(dim));
+    }
+    let v = generate_random_vector(dim);
+    let id = hnsw.insert(&v);
+
+    // Scaled copy has cosine distance 0 to the original
+    let scaled: Vec<f32> = v.iter().map(|x| x * 3.0).collect();
+    let results = hnsw.search(&scaled, 1, None);
+    assert_eq!(results[0].1, id);
+    assert!(results[0].0.abs() < 1e-5);
+
+    // ef_search smaller than k still returns k results
+    assert_eq!(hnsw.search(&v, 10, Some(2)).len(), 10);
+}
+
+#[test]
+fn test_seeded_build_is_deterministic() {
+    let dim = 16;
+    let vectors: Vec<Vec<f32>> = (0..300).map(|_| generate_random_vector(dim)).collect();
+
+    let build = || {
+        let mut hnsw = HnswConfig::new(dim).m(8).seed(42).build().unwrap();
+        for v in &vectors {
+            hnsw.insert(v);
+        }
+        hnsw
+    };
+
+    let a = build();
+    let b = build();
+    assert_eq!(a.layers, b.layers);
+    assert_eq!(a.entry_point, b.entry_point);
+
+    let bytes_a = rkyv::to_bytes::<rkyv::rancor::Error>(&a).unwrap();
+    let bytes_b = rkyv::to_bytes::<rkyv::rancor::Error>(&b).unwrap();
+    assert_eq!(bytes_a.as_slice(), bytes_b.as_slice());
+}
+
+#[test]
+fn test_range_search() {
+    let dim = 8;
+    let mut hnsw = HnswConfig::new(dim).m(8).seed(7).build().unwrap();
+    let vectors = seeded_vectors(501, dim, 28);
+    for v in &vectors[..500] {
+        hnsw.insert(v);
+    }
+
+    let query = &vectors[500];
+    // radius that captures roughly the 30 closest points
+    let radius = hnsw.brute_force_search(query, 30)[29].0;
+
+    let exact = hnsw.brute_force_range_search(query, radius);
+    let approx = hnsw.range_search(query, radius, usize::MAX);
+    assert_eq!(exact.len(), 30);
+    assert!(approx.iter().all(|(d, _)| *d <= radius));
+    assert!(approx.windows(2).all(|w| w[0].0 <= w[1].0));
+    assert!(approx.len() >= 27, "range search found {} of 30", approx.len());
+
+    assert_eq!(hnsw.range_search(query, radius, 5).len(), 5);
+    assert!(hnsw.range_search(query, -1.0, 10).is_empty());
+}
+
+#[test]
+fn test_search_batch_matches_sequential() {
+    let dim = 16;
+    let mut hnsw = HnswConfig::new(dim).seed(3).build().unwrap();
+    for _ in 0..300 {
+        hnsw.insert(&generate_random_vector(dim));
+    }
+
+    let queries: Vec<Vec<f32>> = (0..25).map(|_| generate_random_vector(dim)).collect();
+    let flat: Vec<f32> = queries.iter().flatten().copied().collect();
+
+    let batch = hnsw.search_batch(&flat, 5, Some(50));
+    assert_eq!(batch.len(), queries.len());
+    for (query, row) in queries.iter().zip(&batch) {
+        assert_eq!(row, &hnsw.search(query, 5, Some(50)));
+    }
+    assert!(hnsw.search_batch(&[], 5, None).is_empty());
+}
+
+#[test]
+fn test_search_stats() {
+    let dim = 16;
+    let mut hnsw = HnswConfig::new(dim).m(8).seed(11).build().unwrap();
+    for _ in 0..400 {
+        hnsw.insert(&generate_random_vector(dim));
+    }
+
+    let query = generate_random_vector(dim);
+    let (results, stats) = hnsw.search_with_stats(&query, 10, Some(40));
+    assert_eq!(results, hnsw.search(&query, 10, Some(40)));
+
+    let top = hnsw.layers.node_level(hnsw.entry_point.unwrap());
+    assert_eq!(stats.layers.len(), top + 1);
+    assert_eq!(stats.layers[0].entry_point, hnsw.entry_point.unwrap());
+    assert_eq!(stats.layers.last().unwrap().layer, 0);
+    assert_eq!(stats.layers.last().unwrap().found, 40);
+    assert!(stats.layers.iter().all(|l| l.visited >= 1 && l.visited == l.distance_evaluations));
+    assert_eq!(stats.distance_evaluations, stats.layers.iter().map(|l| l.distance_evaluations).sum::<usize>());
+    assert!(stats.hops > 0);
+}
+
+#[test]
+fn test_graph_diagnostics() {
+    let dim = 8;
+    let mut hnsw = HnswConfig::new(dim).m(6).seed(5).build().unwrap();
+    for _ in 0..300 {
+        hnsw.insert(&generate_random_vector(dim));
+    }
+
+    let diag = hnsw.diagnostics();
+    assert!(hnsw.validate().is_ok(), "{}", diag);
+    assert_eq!(diag.node_count, 300);
+    assert_eq!(diag.layers[0].node_count, 300);
+    assert_eq!(diag.layers[0].degree_histogram.iter().sum::<usize>(), 300);
+    assert_eq!(diag.layers.len(), hnsw.layers.upper_layers.len() + 1);
+    assert!(diag.to_string().contains("Status: OK"));
+
+    // Corrupt the graph: self loop, duplicate edge, dangling edge
+    hnsw.layers.base_layer[0].push(0);
+    let first = hnsw.layers.base_layer[1][0];
+    hnsw.layers.base_layer[1].push(first);
+    hnsw.layers.base_layer[2].push(10_000);
+
+    let diag = hnsw.diagnostics();
+    assert_eq!(diag.layers[0].self_loops, vec![0]);
+    assert_eq!(diag.layers[0].duplicate_edges.len(), 1);
+    assert_eq!(diag.layers[0].dangling_edges, vec![(2, 10_000)]);
+    assert!(hnsw.validate().is_err());
+}
+
+#[test]
+fn test_repair_reconnects_orphans() {
+    let dim = 8;
+    let mut hnsw = HnswConfig::new(dim).m(6).seed(9).build().unwrap();
+    for _ in 0..300 {
+        hnsw.insert(&generate_random_vector(dim));
+    }
+
+    // Cut every edge pointing at a handful of nodes
+    let orphans: Vec<usize> = (0..300).filter(|&n| Some(n) != hnsw.entry_point).step_by(37).collect();
+    for list in hnsw.layers.base_layer.iter_mut() {
+        list.retain(|n| !orphans.contains(n));
+    }
+    for layer in hnsw.layers.upper_layers.iter_mut() {
+        for list in layer.values_mut() {
+            list.retain(|n| !orphans.contains(n));
+        }
+    }
+    assert!(hnsw.diagnostics().unreachable().len() >= orphans.len());
+
+    let report = hnsw.repair();
+    assert!(report.unreachable_before >= orphans.len());
+    assert_eq!(report.unreachable_after, 0);
+    assert!(hnsw.validate().is_ok());
+    assert!(hnsw.diagnostics().layers.iter().all(|l| l.unreachable.is_empty()));
+
+    for &orphan in &orphans {
+        let v = hnsw.vectors.get(orphan).to_vec();
+        assert_eq!(hnsw.search(&v, 1, Some(64))[0].1, orphan);
+    }
+}
+
+#[test]
+fn test_rebuild_keeps_vectors() {
+    let dim = 8;
+    let mut hnsw = HnswConfig::new(dim).m(4).seed(1).build().unwrap();
+    for _ in 0..200 {
+        hnsw.insert(&generate_random_vector(dim));
+    }
+    let data = hnsw.vectors.data.clone();
+
+    assert!(hnsw.rebuild(HnswConfig::new(dim + 1)).is_err());
+
+    hnsw.rebuild(HnswConfig::new(dim).m(12).ef_construction(100).seed(1)).unwrap();
+    assert_eq!(hnsw.config.m, 12);
+    assert_eq!(hnsw.vectors.data, data);
+    assert_eq!(hnsw.layers.base_layer.len(), 200);
+    assert!(hnsw.validate().is_ok());
+
+    let query = hnsw.vectors.get(42).to_vec();
+    assert_eq!(hnsw.search(&query, 1, None)[0].1, 42);
+}
+
+#[test]
+fn test_delete_update_compact() {
+    let dim = 8;
+    let mut hnsw = HnswConfig::new(dim).m(8).seed(21).build().unwrap();
+    let mut vectors = seeded_vectors(302, dim, 33);
+    let (replacement, extra) = (vectors.pop().unwrap(), vectors.pop().unwrap());
+    for v in &vectors {
+        hnsw.insert(v);
+    }
+
+    // delete every third point, supersede a few others
+    for id in (0..300).step_by(3) {
+        assert!(hnsw.delete(id));
+    }
+    assert!(!hnsw.delete(0));
+    assert!(hnsw.update(1, &replacement));
+    assert_eq!(hnsw.len(), 200);
+    assert_eq!(hnsw.deleted_count(), 101);
+
+    let results = hnsw.search(&vectors[3], 10, Some(64));
+    assert!(results.iter().all(|(_, id)| id % 3 != 0));
+    // deleted points don't take up room in the beam, k live hits even with ef == k.
+    // That needs every live point reachable, which HNSW alone doesn't promise
+    hnsw.repair();
+    assert_eq!(hnsw.search(&vectors[3], 10, Some(10)).len(), 10);
+    let all = hnsw.search(&vectors[3], 250, None);
+    assert_eq!(all.len(), hnsw.brute_force_search(&vectors[3], 250).len());
+    assert!(hnsw.brute_force_search(&vectors[3], 300).iter().all(|(_, id)| id % 3 != 0));
+
+    let before = rkyv::to_bytes::<rkyv::rancor::Error>(&hnsw).unwrap().len();
+    assert_eq!(hnsw.compact(), Ok(101));
+    let after = rkyv::to_bytes::<rkyv::rancor::Error>(&hnsw).unwrap().len();
+    assert!(after < before);
+
+    assert_eq!(hnsw.vectors.len(), 200);
+    assert_eq!(hnsw.deleted_count(), 0);
+    assert!(hnsw.validate().is_ok());
+    assert_eq!(hnsw.diagnostics().unreachable().len(), 0);
+
+    // external ids survive the renumbering
+    assert_eq!(hnsw.search(&vectors[4], 1, None)[0].1, 4);
+    assert_eq!(hnsw.search(&replacement, 1, None)[0].1, 1);
+    assert_eq!(hnsw.insert(&extra), 300);
+
+    // a tiered store whose file can't be rewritten: an error, and nothing removed
+    let dir = std::env::temp_dir().join("photon_test_compact");
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir(&dir).unwrap();
+    hnsw.delete(4);
+    hnsw.set_tier(photon_db::TierConfig::new(dir.join("vectors.f32").to_str().unwrap())).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(hnsw.compact().is_err());
+    assert_eq!(hnsw.deleted_count(), 1);
+    hnsw.validate().unwrap();
+    assert_eq!(hnsw.search(&replacement, 1, None)[0].1, 1);
+}
+
+#[test]
+fn test_vector_retrieval_and_export() {
+    let dim = 3;
+    let mut hnsw = HNSW::new(10, dim);
+    let mut payload = Payload::new();
+    payload.insert("title".to_string(), PayloadValue::from("first"));
+    let a = hnsw.insert_with_payload(&[1.0, 2.0, 3.0], payload.clone());
+    let b = hnsw.insert(&[4.0, 5.0, 6.0]);
+    let c = hnsw.insert(&[7.0, 8.0, 9.0]);
+    hnsw.delete(b);
+
+    assert_eq!(hnsw.get_vector(a), Some(&[1.0, 2.0, 3.0][..]));
+    assert_eq!(hnsw.get_vector(b), None);
+    assert_eq!(hnsw.get_vectors(&[c, b]), vec![Some(&[7.0, 8.0, 9.0][..]), None]);
+    assert_eq!(hnsw.payload(a), Some(&payload));
+
+    let items: Vec<_> = hnsw.iter().collect();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0], (a, &[1.0, 2.0, 3.0][..], Some(&payload)));
+    assert_eq!(items[1], (c, &[7.0, 8.0, 9.0][..], None));
+
+    let temp_dir = std::env::temp_dir().join("photon_test_export");
+    fs::create_dir_all(&temp_dir).unwrap();
+
+    let fvecs = temp_dir.join("vectors.fvecs");
+    assert_eq!(hnsw.export(&fvecs).unwrap(), vec![a, c]);
+    let bytes = fs::read(&fvecs).unwrap();
+    assert_eq!(bytes.len(), 2 * (4 + dim * 4));
+    assert_eq!(i32::from_le_bytes(bytes[0..4].try_into().unwrap()), dim as i32);
+    assert_eq!(f32::from_le_bytes(bytes[20..24].try_into().unwrap()), 7.0);
+
+    let npy = temp_dir.join("vectors.npy");
+    assert_eq!(hnsw.export(&npy).unwrap(), vec![a, c]);
+    let bytes = fs::read(&npy).unwrap();
+    assert_eq!(&bytes[..6], b"\x93NUMPY");
+    let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+    assert_eq!((10 + header_len) % 64, 0);
+    assert!(String::from_utf8_lossy(&bytes[10..10 + header_len]).contains("'shape': (2, 3)"));
+    assert_eq!(bytes.len(), 10 + header_len + 2 * dim * 4);
+
+    assert!(hnsw.export(&temp_dir.join("vectors.csv")).is_err());
+    fs::remove_dir_all(temp_dir).unwrap();
+}
+
+#[test]
+fn test_search_by_id() {
+    let dim = 8;
+    let mut hnsw = HnswConfig::new(dim).seed(13).build().unwrap();
+    for _ in 0..300 {
+        hnsw.insert(&generate_random_vector(dim));
+    }
+    let source = 17;
+
+    let results = hnsw.search_by_id(source, 10, Some(64), &[]).unwrap();
+    assert_eq!(results.len(), 10);
+    assert!(results.iter().all(|(_, id)| *id != source));
+
+    // same neighbors as a plain search minus the source itself (beam is widened by one excluded id)
+    let query = hnsw.get_vector(source).unwrap().to_vec();
+    let plain: Vec<usize> = hnsw.search(&query, 11, Some(65)).into_iter().map(|(_, id)| id).filter(|&id| id != source).collect();
+    let by_id: Vec<usize> = results.iter().map(|(_, id)| *id).collect();
+    assert_eq!(by_id, plain[..10]);
+
+    let exclude = vec![by_id[0], by_id[1]];
+    let filtered = hnsw.search_by_id(source, 10, Some(64), &exclude).unwrap();
+    assert_eq!(filtered.len(), 10);
+    assert!(filtered.iter().all(|(_, id)| *id != source && !exclude.contains(id)));
+
+    hnsw.delete(source);
+    assert!(hnsw.search_by_id(source, 10, None, &[]).is_none());
+}
+
+#[test]
+fn test_recommend() {
+    let dim = 2;
+    let mut hnsw = HnswConfig::new(dim).seed(4).build().unwrap();
+    // two clusters far apart
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    for i in 0..50 {
+        let jitter = i as f32 * 0.01;
+        left.push(hnsw.insert(&[-10.0 + jitter, jitter]));
+        right.push(hnsw.insert(&[10.0 + jitter, jitter]));
+    }
+
+    for strategy in [RecommendStrategy::AverageVector, RecommendStrategy::BestScore] {
+        let results = hnsw.recommend(&[left[0], left[1]], &[right[0]], 5, strategy).unwrap();
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|(_, id)| left.contains(id)), "{:?}", strategy);
+        assert!(results.iter().all(|(_, id)| ![left[0], left[1], right[0]].contains(id)));
+    }
+
+    let results = hnsw
+        .recommend_vectors(&[vec![10.0, 0.0]], &[vec![-10.0, 0.0]], 3, RecommendStrategy::BestScore)
+        .unwrap();
+    assert!(results.iter().all(|(_, id)| right.contains(id)));
+
+    assert!(hnsw.recommend(&[], &[left[0]], 5, RecommendStrategy::AverageVector).is_err());
+    assert!(hnsw.recommend(&[999], &[], 5, RecommendStrategy::AverageVector).is_err());
+    assert!(hnsw.recommend_vectors(&[vec![1.0]], &[], 5, RecommendStrategy::BestScore).is_err());
+}
+
+#[test]
+fn test_search_mmr_diversifies() {
+    let dim = 2;
+    let mut hnsw = HnswConfig::new(dim).seed(8).build().unwrap();
+    // a tight clump of near duplicates right next to the query, plus a ring of distinct points
+    let clump: Vec<usize> = (0..10).map(|i| hnsw.insert(&[1.0 + i as f32 * 1e-3, 0.0])).collect();
+    for i in 0..40 {
+        let angle = i as f32 * std::f32::consts::TAU / 40.0;
+        hnsw.insert(&[2.0 * angle.cos(), 2.0 * angle.sin()]);
+    }
+
+    let query = [1.0, 0.0];
+    let plain = hnsw.search(&query, 5, None);
+    assert!(plain.iter().all(|(_, id)| clump.contains(id)));
+
+    // lambda = 1 is plain relevance order
+    let same: Vec<usize> = hnsw.search_mmr(&query, 5, 20, 1.0).unwrap().iter().map(|(_, id)| *id).collect();
+    assert_eq!(same, plain.iter().map(|(_, id)| *id).collect::<Vec<_>>());
+
+    // query sits on the clump, so with squared L2 relevance and redundancy cancel out at 0.5
+    let diverse = hnsw.search_mmr(&query, 5, 20, 0.3).unwrap();
+    assert_eq!(diverse.len(), 5);
+    assert_eq!(diverse[0].1, plain[0].1);
+    let from_clump = diverse.iter().filter(|(_, id)| clump.contains(id)).count();
+    assert!(from_clump <= 2, "MMR kept {} near duplicates", from_clump);
+    assert!(hnsw.search_mmr(&query, 5, 20, 1.5).is_err());
+}
+
+fn text_payload(text: &str) -> Payload {
+    let mut payload = Payload::new();
+    payload.insert("text".to_string(), PayloadValue::from(text));
+    payload
+}
+
+#[test]
+fn test_bm25_and_hybrid_search() {
+    let dim = 4;
+    let mut hnsw = HnswConfig::new(dim).seed(2).build().unwrap();
+    let docs = [
+        "the printer shows error E1234 when the tray is empty",
+        "restart the router to fix slow wifi",
+        "the router firmware update failed",
+        "printer paper jam in tray two",
+    ];
+    // the keyword doc sits far away from the vector query used below
+    let ids: Vec<usize> = docs
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let vector = match i {
+                0 => vec![10.0; dim],
+                3 => vec![-10.0; dim],
+                _ => generate_random_vector(dim),
+            };
+            hnsw.insert_with_payload(&vector, text_payload(d))
+        })
+        .collect();
+    for _ in 0..50 {
+        hnsw.insert(&generate_random_vector(dim));
+    }
+
+    let hits = hnsw.text_search("error e1234", 10);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].1, ids[0]);
+
+    let hits = hnsw.text_search("router", 10);
+    assert_eq!(hits.len(), 2);
+    assert!(hits.iter().all(|(_, id)| *id == ids[1] || *id == ids[2]));
+
+    // exact keyword match wins once text gets most of the weight, vector hit wins the other way round
+    let vector = hnsw.get_vector(ids[3]).unwrap().to_vec();
+    for fusion in [Fusion::Rrf, Fusion::Weighted] {
+        assert_eq!(hnsw.hybrid_search("E1234", &vector, 3, 0.2, fusion)[0].1, ids[0]);
+        assert_eq!(hnsw.hybrid_search("E1234", &vector, 3, 0.8, fusion)[0].1, ids[3]);
+    }
+
+    // index follows payload changes and deletes
+    hnsw.set_payload(ids[1], text_payload("switch"));
+    let hits = hnsw.text_search("router", 10);
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].1, ids[2]);
+    hnsw.delete(ids[0]);
+    assert!(hnsw.text_search("e1234", 10).is_empty());
+
+    // rebuild keeps payloads and the text index
+    hnsw.rebuild(HnswConfig::new(dim).seed(3)).unwrap();
+    assert_eq!(hnsw.text_search("switch", 1)[0].1, ids[1]);
+
+    // and is saved with the rest of the index
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&hnsw).unwrap();
+    let loaded = rkyv::from_bytes::<HNSW, rkyv::rancor::Error>(&bytes).unwrap();
+    assert_eq!(loaded.text_index, hnsw.text_index);
+    assert_eq!(loaded.text_search("switch", 1)[0].1, ids[1]);
+}
+
+#[test]
+fn test_ingest_chunkers() {
+    use photon_db::ingest::{chunk_dir, walk, Chunker};
+
+    let dir = std::env::temp_dir().join("photon_test_ingest");
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(dir.join("notes")).unwrap();
+    let txt = "first paragraph is long enough\n\n  tiny\n\nsecond paragraph, also long enough\n";
+    let md = "# Guide\nintro text\n\n## Setup\nrun the installer\n```\n# not a heading\n```\n## Usage\ncall search()\n";
+    fs::write(dir.join("a.txt"), txt).unwrap();
+    fs::write(dir.join("notes/b.md"), md).unwrap();
+    fs::write(dir.join("ignored.rs"), "fn main() {}").unwrap();
+
+    // a link back up the tree isn't followed
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&dir, dir.join("notes/loop")).unwrap();
+
+    let files = walk(&dir).unwrap();
+    assert_eq!(files, vec![dir.join("a.txt"), dir.join("notes/b.md")]);
+
+    // offsets always point back into the file
+    let check = |chunks: &[photon_db::Chunk]| {
+        for c in chunks {
+            let source = fs::read_to_string(&c.source).unwrap();
+            assert_eq!(&source[c.start..c.end], c.text);
+        }
+    };
+
+    let paragraphs = chunk_dir(&dir, &Chunker::Paragraph { min_len: 20 }).unwrap();
+    check(&paragraphs);
+    let texts: Vec<&str> = paragraphs.iter().filter(|c| c.source.ends_with("a.txt")).map(|c| c.text.as_str()).collect();
+    assert_eq!(texts, vec!["first paragraph is long enough", "second paragraph, also long enough"]);
+
+    let windows = chunk_dir(&dir, &Chunker::TokenWindow { size: 4, overlap: 2 }).unwrap();
+    check(&windows);
+    assert_eq!(windows[0].text, "first paragraph is long");
+    assert_eq!(windows[1].text, "is long enough\n\n  tiny");
+    assert!(Chunker::TokenWindow { size: 2, overlap: 2 }.validate().is_err());
+    // split checks too instead of looping forever / underflowing
+    let src = std::path::Path::new("a.txt");
+    assert!(Chunker::TokenWindow { size: 3, overlap: 3 }.split(src, "a b c d").is_err());
+    assert!(Chunker::TokenWindow { size: 2, overlap: 5 }.split(src, "a b c d").is_err());
+    assert!(Chunker::Markdown { max_len: 0 }.split(src, "# a\nb").is_err());
+    assert_eq!(Chunker::TokenWindow { size: 3, overlap: 2 }.split(src, "a b c d").unwrap().len(), 2);
+
+    let sections = chunk_dir(&dir, &Chunker::Markdown { max_len: 1000 }).unwrap();
+    check(&sections);
+    let headings: Vec<Option<&str>> = sections
+        .iter()
+        .filter(|c| c.source.ends_with("b.md"))
+        .map(|c| c.heading.as_deref())
+        .collect();
+    assert_eq!(headings, vec![Some("Guide"), Some("Guide > Setup"), Some("Guide > Usage")]);
+    assert!(sections.iter().any(|c| c.text.contains("# not a heading")));
+
+    // embeddings come from the caller, chunks land with their metadata
+    let mut db = PhotonDB::create(dir.join("db.pho"), 100, 2).unwrap();
+    let ids = db
+        .ingest_dir(&dir, &Chunker::Markdown { max_len: 1000 }, |texts| {
+            Ok(texts.iter().map(|t| vec![t.len() as f32, t.lines().count() as f32]).collect())
+        })
+        .unwrap();
+    assert_eq!(ids.len(), sections.len());
+    // a.txt has no headings so it is a single chunk, b.md starts at ids[1]
+    assert_eq!(db.hnsw().unwrap().payload(ids[0]).unwrap().get("heading"), None);
+    let payload = db.hnsw().unwrap().payload(ids[1]).unwrap();
+    assert_eq!(payload["heading"].as_str(), Some("Guide"));
+    assert_eq!(payload["start"].as_int(), Some(0));
+    assert!(payload["source"].as_str().unwrap().ends_with("b.md"));
+    assert_eq!(db.hnsw().unwrap().text_search("installer", 1)[0].1, ids[2]);
+    assert!(db.ingest(&sections[..1], &[]).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_embedders() {
+    use photon_db::{Embedder, HashingEmbedder, WordVectors};
+
+    let hashing = HashingEmbedder::new(256);
+    let a = hashing.embed("database indexing").unwrap();
+    assert_eq!(a, hashing.embed("database indexing").unwrap());
+    assert!((a.iter().map(|x| x * x).sum::<f32>() - 1.0).abs() < 1e-5);
+    let near = Metric::Cosine.distance(&a, &hashing.embed("indexing databases").unwrap());
+    let far = Metric::Cosine.distance(&a, &hashing.embed("chocolate cake").unwrap());
+    assert!(near < far);
+
+    // word2vec text format: "count dim" header, then one word per line
+    let path = std::env::temp_dir().join("photon_test_word_vectors.txt");
+    fs::write(&path, "3 2\nCat 1.0 0.0\ndog 0.0 1.0\ncar 1.0 1.0\n").unwrap();
+    let words = WordVectors::load(&path).unwrap();
+    assert_eq!(words.dim, 2);
+    assert_eq!(words.embed("cat DOG unknown").unwrap(), vec![0.5, 0.5]);
+    assert_eq!(words.embed("nothing known").unwrap(), vec![0.0, 0.0]);
+    fs::write(&path, "cat 1.0 0.0\ndog 1.0\n").unwrap();
+    assert!(WordVectors::load(&path).is_err());
+    fs::remove_file(&path).unwrap();
+
+    let dir = std::env::temp_dir().join("photon_test_embedder");
+    let mut db = PhotonDB::create_with_config(dir.join("db.pho"), HnswConfig::new(128).metric(Metric::Cosine)).unwrap();
+    assert!(db.add_text("no embedder yet").is_err());
+    assert!(db.set_embedder(Box::new(HashingEmbedder::new(64))).is_err());
+    db.set_embedder(Box::new(HashingEmbedder::new(128))).unwrap();
+    let docs = ["rust vector database", "baking sourdough bread", "graph based nearest neighbor search"];
+    let ids: Vec<usize> = docs.iter().map(|d| db.add_text(d).unwrap()).collect();
+    assert_eq!(db.search_text("sourdough baking", 1).unwrap()[0].1, ids[1]);
+    assert_eq!(db.hnsw().unwrap().payload(ids[2]).unwrap()["text"].as_str(), Some(docs[2]));
+}
+
+#[test]
+fn test_retrieve_context() {
+    use photon_db::WhitespaceTokens;
+
+    let chunk = |text: &str, source: &str, start: i64| {
+        let mut payload = text_payload(text);
+        payload.insert("source".to_string(), PayloadValue::from(source));
+        payload.insert("start".to_string(), PayloadValue::Int(start));
+        payload.insert("end".to_string(), PayloadValue::Int(start + text.len() as i64));
+        payload
+    };
+    let mut hnsw = HNSW::new(10, 2);
+    let a0 = hnsw.insert_with_payload(&[0.0, 0.0], chunk("alpha beta", "a.md", 0));
+    let a1 = hnsw.insert_with_payload(&[0.1, 0.0], chunk("gamma delta", "a.md", 12));
+    let a2 = hnsw.insert_with_payload(&[5.0, 0.0], chunk("far away chunk", "a.md", 100));
+    let b0 = hnsw.insert_with_payload(&[1.0, 0.0], chunk("other file", "b.md", 0));
+    hnsw.insert(&[0.0, 0.1]); // no text, never shows up
+
+    // a0 and a1 are 2 bytes apart in a.md so they become one block
+    let context = hnsw.retrieve_context(&[0.0, 0.0], 100, 5, &WhitespaceTokens);
+    assert_eq!(context.text, "[1] alpha beta\n\ngamma delta\n\n[2] other file\n\n[3] far away chunk");
+    assert_eq!(context.tokens, 12);
+    let first = &context.citations[0];
+    assert_eq!(first.ids, vec![a0, a1]);
+    assert_eq!((first.source.as_deref(), first.start, first.end), (Some("a.md"), Some(0), Some(23)));
+    assert_eq!(context.citations[1].ids, vec![b0]);
+    assert_eq!(context.citations[2].ids, vec![a2]);
+    assert!(context.citations.iter().all(|c| !c.truncated));
+
+    // the last block gets cut at a word boundary and its end offset follows
+    let context = hnsw.retrieve_context(&[0.0, 0.0], 10, 5, &WhitespaceTokens);
+    assert!(context.text.ends_with("[3] far"));
+    assert_eq!(context.tokens, 10);
+    let last = context.citations.last().unwrap();
+    assert!(last.truncated);
+    assert_eq!((last.start, last.end), (Some(100), Some(103)));
+
+    // custom counter: one token per byte
+    let context = hnsw.retrieve_context(&[0.0, 0.0], 12, 5, &|t: &str| t.len());
+    assert_eq!(context.text, "[1] alpha");
+    assert_eq!(context.citations.len(), 1);
+    assert_eq!(context.citations[0].end, Some(5));
+
+    // spans that don't match the text (hand-set payloads, multibyte chars) are never merged
+    let mut hnsw = HNSW::new(10, 2);
+    hnsw.insert_with_payload(&[0.0, 0.0], chunk("abcde", "c.md", 0));
+    hnsw.insert_with_payload(&[0.1, 0.0], chunk("éé", "c.md", 4));
+    let mut payload = chunk("zz", "c.md", 2);
+    payload.insert("end".to_string(), PayloadValue::Int(30));
+    hnsw.insert_with_payload(&[0.2, 0.0], payload);
+    let context = hnsw.retrieve_context(&[0.0, 0.0], 100, 5, &WhitespaceTokens);
+    assert_eq!(context.citations.len(), 3);
+    assert!(context.text.contains("éé") && context.text.contains("zz"));
+}
+
+#[test]
+fn test_search_grouped() {
+    // 20 documents along a line, 10 tight chunks each, ef_search small enough that a
+    // single beam only ever sees one or two documents
+    let mut hnsw = HnswConfig::new(2).ef_search(8).seed(4).build().unwrap();
+    let mut rng = rand::rng();
+    for doc in 0..20i64 {
+        for _ in 0..10 {
+            let mut payload = Payload::new();
+            payload.insert("doc".to_string(), PayloadValue::Int(doc));
+            let v = [doc as f32 * 10.0 + rng.random::<f32>(), rng.random::<f32>()];
+            hnsw.insert_with_payload(&v, payload);
+        }
+    }
+    hnsw.insert(&[0.0, 0.0]); // no "doc", ignored
+
+    let groups = hnsw.search_grouped(&[52.0, 0.5], "doc", 5, 2);
+    let keys: Vec<i64> = groups.iter().map(|g| g.key.as_int().unwrap()).collect();
+    assert_eq!(keys, vec![5, 6, 4, 7, 3]);
+    for g in &groups {
+        assert_eq!(g.hits.len(), 2);
+        assert!(g.hits[0].0 <= g.hits[1].0);
+        for &(_, id) in &g.hits {
+            assert_eq!(hnsw.payload(id).unwrap()["doc"], g.key);
+        }
+    }
+
+    // more groups than documents: everything, once
+    assert_eq!(hnsw.search_grouped(&[52.0, 0.5], "doc", 50, 1).len(), 20);
+    assert!(hnsw.search_grouped(&[52.0, 0.5], "missing", 3, 1).is_empty());
+}
+
+#[test]
+fn test_sparse_vectors() {
+    use photon_db::SparseVector;
+
+    let v = SparseVector::new(&[(7, 1.0), (2, 0.5), (7, 1.0), (3, 0.0)]);
+    assert_eq!(v.indices, vec![2, 7]);
+    assert_eq!(v.values, vec![0.5, 2.0]);
+    assert_eq!(v.dot(&SparseVector::new(&[(7, 2.0), (9, 5.0)])), 4.0);
+
+    let dim = 4;
+    let mut hnsw = HnswConfig::new(dim).seed(5).build().unwrap();
+    let mut rng = StdRng::seed_from_u64(43);
+    let mut sparse = Vec::new();
+    for _ in 0..200 {
+        let pairs: Vec<(u32, f32)> = (0..5).map(|_| (rng.random_range(0..50), rng.random::<f32>())).collect();
+        let s = SparseVector::new(&pairs);
+        let dense: Vec<f32> = (0..dim).map(|_| rng.random::<f32>()).collect();
+        let id = hnsw.insert_with_sparse(&dense, s.clone());
+        sparse.push((id, s));
+    }
+
+    // inverted lists give the exact dot product ranking
+    let query = SparseVector::new(&[(3, 1.0), (10, 0.5), (42, 2.0)]);
+    let mut expected: Vec<(f32, usize)> = sparse
+        .iter()
+        .map(|(id, s)| (s.dot(&query), *id))
+        .filter(|(score, _)| *score > 0.0)
+        .collect();
+    expected.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then(a.1.cmp(&b.1)));
+    expected.truncate(10);
+    let got = hnsw.sparse_search(&query, 10);
+    assert_eq!(got.iter().map(|h| h.1).collect::<Vec<_>>(), expected.iter().map(|h| h.1).collect::<Vec<_>>());
+    for (g, e) in got.iter().zip(&expected) {
+        assert!((g.0 - e.0).abs() < 1e-5);
+    }
+
+    // hybrid: text-like side wins at low alpha, dense side at high alpha
+    let top_sparse = got[0].1;
+    let dense_id = sparse.iter().map(|(id, _)| *id).find(|&id| id != top_sparse).unwrap();
+    let dense_target = hnsw.get_vector(dense_id).unwrap().to_vec();
+    assert_eq!(hnsw.sparse_hybrid_search(&dense_target, &query, 1, 0.0, Fusion::Rrf)[0].1, top_sparse);
+    assert_eq!(hnsw.sparse_hybrid_search(&dense_target, &query, 1, 1.0, Fusion::Rrf)[0].1, dense_id);
+
+    assert!(!hnsw.set_sparse(10_000, query.clone()));
+    hnsw.delete(top_sparse);
+    assert!(hnsw.get_sparse(top_sparse).is_none());
+    assert!(hnsw.sparse_search(&query, 200).iter().all(|h| h.1 != top_sparse));
+
+    hnsw.compact().unwrap();
+    hnsw.rebuild(HnswConfig::new(dim).seed(6)).unwrap();
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&hnsw).unwrap();
+    let loaded = rkyv::from_bytes::<HNSW, rkyv::rancor::Error>(&bytes).unwrap();
+    assert_eq!(loaded.sparse_index, hnsw.sparse_index);
+    let kept = sparse.iter().find(|(id, _)| *id == dense_id).unwrap();
+    assert_eq!(loaded.get_sparse(kept.0), Some(&kept.1));
+    assert_eq!(loaded.sparse_search(&query, 5), hnsw.sparse_search(&query, 5));
+}
+
+#[test]
+fn test_multi_vector_maxsim() {
+    use photon_db::MultiVectorIndex;
+
+    let dim = 8;
+    let mut index = MultiVectorIndex::new(HnswConfig::new(dim).metric(Metric::Cosine).seed(8)).unwrap();
+    let mut rng = rand::rng();
+    let mut docs = Vec::new();
+    for _ in 0..100 {
+        let n = rng.random_range(3..8);
+        let tokens: Vec<Vec<f32>> = (0..n).map(|_| generate_random_vector(dim).iter().map(|x| x - 0.5).collect()).collect();
+        docs.push((index.insert_multi(&tokens).unwrap(), tokens));
+    }
+    assert_eq!(index.len(), 100);
+    assert!(index.insert_multi(&[]).is_err());
+    assert!(index.insert_multi(&[vec![1.0; dim + 1]]).is_err());
+
+    // a few tokens of doc 42, slightly perturbed
+    let (target, tokens) = &docs[42];
+    let query: Vec<Vec<f32>> = tokens.iter().take(3).map(|t| t.iter().map(|x| x + 0.01).collect()).collect();
+
+    let brute: Vec<(f32, usize)> = {
+        let mut all: Vec<(f32, usize)> = docs.iter().map(|(id, _)| (index.maxsim(&query, *id).unwrap(), *id)).collect();
+        all.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        all
+    };
+    let results = index.search_multi(&query, 5).unwrap();
+    assert_eq!(results[0].1, *target);
+    assert_eq!(brute[0].1, *target);
+    // every token matches itself: score close to 3 (cosine similarity per query token)
+    assert!((results[0].0 - 3.0).abs() < 0.05);
+    for w in results.windows(2) {
+        assert!(w[0].0 >= w[1].0);
+    }
+
+    assert!(index.delete(*target));
+    assert!(!index.delete(*target));
+    assert!(index.get_multi(*target).is_none());
+    assert!(index.search_multi(&query, 5).unwrap().iter().all(|r| r.1 != *target));
+
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&index).unwrap();
+    let loaded = rkyv::from_bytes::<MultiVectorIndex, rkyv::rancor::Error>(&bytes).unwrap();
+    assert_eq!(loaded.get_multi(docs[0].0).unwrap().len(), docs[0].1.len());
+    assert_eq!(loaded.search_multi(&query, 5).unwrap(), index.search_multi(&query, 5).unwrap());
+}
+
+#[test]
+fn test_named_vectors() {
+    use photon_db::Collection;
+
+    let mut collection = Collection::new();
+    collection.add_space("title", HnswConfig::new(4).seed(1)).unwrap();
+    collection.add_space("body", HnswConfig::new(16).metric(Metric::Cosine).seed(2)).unwrap();
+    assert!(collection.add_space("title", HnswConfig::new(4)).is_err());
+
+    let mut points = Vec::new();
+    for i in 0..100 {
+        let title = generate_random_vector(4);
+        let body = generate_random_vector(16);
+        let mut payload = Payload::new();
+        payload.insert("n".to_string(), PayloadValue::Int(i));
+        let id = collection.insert(&[("title", &title), ("body", &body)], Some(payload)).unwrap();
+        points.push((id, title, body));
+    }
+    // title only, never shows up in body searches
+    let title_only = collection.insert(&[("title", &[0.5; 4])], None).unwrap();
+
+    assert!(collection.insert(&[("title", &[0.0; 16])], None).is_err());
+    assert!(collection.insert(&[("nope", &[0.0; 4])], None).is_err());
+    assert_eq!(collection.len(), 101);
+
+    // same id in both spaces
+    let (id, title, body) = &points[17];
+    assert_eq!(collection.search("title", title, 1, None).unwrap()[0].1, *id);
+    assert_eq!(collection.search("body", body, 1, None).unwrap()[0].1, *id);
+    assert_eq!(collection.get_vector(*id, "body"), Some(body.as_slice()));
+    assert!(collection.get_vector(title_only, "body").is_none());
+    assert!(collection.search("body", &[0.5; 16], 200, None).unwrap().iter().all(|r| r.1 != title_only));
+
+    // body added later to the title-only point
+    collection.set_vector(title_only, "body", &[1.0; 16]).unwrap();
+    assert_eq!(collection.search("body", &[1.0; 16], 1, None).unwrap()[0].1, title_only);
+
+    // one delete removes it everywhere, payload included
+    assert!(collection.delete(*id));
+    assert!(collection.payload(*id).is_none());
+    assert!(collection.search("title", title, 5, None).unwrap().iter().all(|r| r.1 != *id));
+    assert!(collection.search("body", body, 5, None).unwrap().iter().all(|r| r.1 != *id));
+    assert!(collection.set_vector(*id, "title", title).is_err());
+
+    assert_eq!(collection.compact(), Ok(2));
+    let (other, _, body) = &points[3];
+    assert_eq!(collection.search("body", body, 1, None).unwrap()[0].1, *other);
+
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&collection).unwrap();
+    let loaded = rkyv::from_bytes::<Collection, rkyv::rancor::Error>(&bytes).unwrap();
+    loaded.validate().unwrap();
+    assert_eq!(loaded, collection);
+    assert_eq!(loaded.payload(*other).unwrap()["n"].as_int(), Some(3));
+}
+
+#[test]
+fn test_flat_index_and_index_trait() {
+    use photon_db::{AnyIndex, FlatIndex, Index, IndexKind};
+
+    let dim = 8;
+    let mut flat = FlatIndex::new(dim, Metric::Euclidean);
+    let mut hnsw = HnswConfig::new(dim).seed(9).build().unwrap();
+    // same code drives both through the trait
+    fn fill(index: &mut dyn Index, vectors: &[Vec<f32>]) -> Vec<usize> {
+        vectors.iter().map(|v| index.insert(v)).collect()
+    }
+    let vectors: Vec<Vec<f32>> = (0..300).map(|_| generate_random_vector(dim)).collect();
+    let flat_ids = fill(&mut flat, &vectors);
+    let hnsw_ids = fill(&mut hnsw, &vectors);
+    assert_eq!(flat_ids, hnsw_ids);
+
+    // flat is exact
+    let query = generate_random_vector(dim);
+    assert_eq!(Index::search(&flat, &query, 10), hnsw.brute_force_search(&query, 10));
+
+    // deletes move the last row into the hole, ids stay put
+    for id in [0, 150, 299, 10] {
+        assert!(Index::delete(&mut flat, id));
+        assert!(Index::delete(&mut hnsw, id));
+    }
+    assert!(!Index::delete(&mut flat, 150));
+    assert_eq!(Index::len(&flat), 296);
+    assert_eq!(Index::get_vector(&flat, 298), Some(vectors[298].as_slice()));
+    assert_eq!(Index::get_vector(&flat, 299), None);
+    assert_eq!(Index::search(&flat, &query, 10), hnsw.brute_force_search(&query, 10));
+
+    let queries: Vec<f32> = (0..5).flat_map(|_| generate_random_vector(dim)).collect();
+    let batch = flat.search_batch(&queries, 3);
+    for (i, q) in queries.chunks(dim).enumerate() {
+        assert_eq!(batch[i], Index::search(&flat, q, 3));
+    }
+
+    // the file remembers the index kind, plain HNSW files from before still load
+    let any: AnyIndex = flat.into();
+    let loaded = AnyIndex::from_bytes(&any.to_bytes().unwrap()).unwrap();
+    assert_eq!(loaded.kind(), IndexKind::Flat);
+    assert_eq!(loaded, any);
+    let legacy = rkyv::to_bytes::<rkyv::rancor::Error>(&hnsw).unwrap();
+    assert_eq!(AnyIndex::from_bytes(&legacy).unwrap().kind(), IndexKind::Hnsw);
+    let hnsw_any: AnyIndex = hnsw.into();
+    assert_eq!(AnyIndex::from_bytes(&hnsw_any.to_bytes().unwrap()).unwrap(), hnsw_any);
+
+    let dir = std::env::temp_dir().join("photon_test_flat");
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir(&dir).unwrap();
+    let mut db = PhotonDB::create_flat(dir.join("db.pho"), dim, Metric::Cosine).unwrap();
+    let id = db.add(&vectors[0]);
+    assert!(db.add_with_payload(&vectors[1], Payload::new()).is_err());
+    assert!(db.hnsw().is_err());
+    db.save().unwrap();
+    let loaded = PhotonDB::load(dir.join("db.pho"), dim).unwrap();
+    assert_eq!(loaded.kind(), IndexKind::Flat);
+    assert_eq!(loaded.search(&vectors[0], 1)[0].1, id);
+
+    // Index::save writes the header too, and load checks it's the right kind
+    let AnyIndex::Flat(mut flat) = any else { unreachable!() };
+    let path = dir.join("flat.pho");
+    flat.save(&path).unwrap();
+    assert_eq!(AnyIndex::load(&path).unwrap().kind(), IndexKind::Flat);
+    assert_eq!(FlatIndex::load(&path).unwrap(), flat);
+    assert!(HNSW::load(&path).unwrap_err().contains("flat"));
+
+    // ids / rows / vectors out of step -> load error instead of a panic in delete
+    flat.ids.push(12345);
+    assert!(flat.validate().is_err());
+    flat.save(&path).unwrap();
+    assert!(FlatIndex::load(&path).is_err());
+    assert!(AnyIndex::load(&path).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_ivf_index() {
+    use photon_db::{AnyIndex, Index, IndexKind, IvfConfig, IvfIndex};
+
+    let dim = 16;
+    let vectors: Vec<Vec<f32>> = (0..2000).map(|_| generate_random_vector(dim)).collect();
+    let queries: Vec<Vec<f32>> = (0..20).map(|_| generate_random_vector(dim)).collect();
+    // recall@10 against the exact scan of the same index
+    fn recall(ivf: &IvfIndex, queries: &[Vec<f32>], nprobe: usize) -> f32 {
+        let mut hits = 0;
+        for q in queries {
+            let truth = ivf.brute_force_search(q, 10);
+            hits += ivf.search_with_nprobe(q, 10, Some(nprobe)).iter().filter(|hit| truth.contains(hit)).count();
+        }
+        hits as f32 / (queries.len() * 10) as f32
+    }
+
+    let mut ivf = IvfConfig::new(dim).nlist(20).nprobe(20).seed(3).build().unwrap();
+    for v in &vectors[..10] {
+        ivf.insert(v);
+    }
+    assert!(ivf.train().is_err(), "fewer vectors than lists");
+    for v in &vectors[10..] {
+        ivf.insert(v);
+    }
+    // untrained = one list, exact
+    assert!(!ivf.is_trained());
+    assert_eq!(ivf.search(&queries[0], 10), ivf.brute_force_search(&queries[0], 10));
+
+    ivf.train().unwrap();
+    assert!(ivf.is_trained());
+    assert_eq!(ivf.lists.len(), 20);
+    ivf.validate().unwrap();
+    // probing every list is exact again, fewer lists trade recall for speed
+    for q in &queries {
+        assert_eq!(ivf.search(q, 10), ivf.brute_force_search(q, 10));
+    }
+    let few = recall(&ivf, &queries, 4);
+    assert!(few > 0.4 && few <= 1.0, "nprobe=4 recall {}", few);
+
+    // deletes and inserts after training keep the lists consistent
+    for id in [0, 500, 1999, 42] {
+        assert!(ivf.delete(id));
+    }
+    assert!(!ivf.delete(500));
+    let id = ivf.insert(&vectors[500]);
+    assert_eq!(id, 2000);
+    ivf.validate().unwrap();
+    assert_eq!(ivf.get_vector(1998), Some(vectors[1998].as_slice()));
+
+    // broken files are caught by validate, not by a panic in search
+    let row = *ivf.lists[3].rows.iter().find(|&&r| r != 1).unwrap();
+    ivf.lists[3].rows.push(row);
+    ivf.lists[ivf.assignment[1]].rows.retain(|&r| r != 1);
+    assert!(ivf.validate().unwrap_err().contains("twice"));
+    ivf.lists[3].rows.pop();
+    ivf.lists[ivf.assignment[1]].rows.push(1);
+    ivf.validate().unwrap();
+    ivf.assignment[5] = 20;
+    assert!(ivf.validate().is_err());
+    ivf.assignment[5] = ivf.lists.iter().position(|l| l.rows.contains(&5)).unwrap();
+    let centroid = ivf.centroids.split_off(19 * dim);
+    assert!(ivf.validate().is_err());
+    ivf.centroids.extend(centroid);
+    ivf.validate().unwrap();
+    assert_eq!(ivf.search(&vectors[500], 1)[0].1, 2000);
+
+    // PQ codes: scanned with the codes, re-ranked on the full vectors
+    let mut pq = IvfConfig::new(dim).metric(Metric::Cosine).nlist(20).nprobe(20).pq(4).iterations(5).seed(3).build().unwrap();
+    for v in &vectors[..1000] {
+        pq.insert(v);
+    }
+    pq.train().unwrap();
+    assert!(pq.lists.iter().all(|l| l.codes.len() == l.rows.len() * 4));
+    let pq_recall = recall(&pq, &queries, 20);
+    assert!(pq_recall > 0.8, "pq recall {}", pq_recall);
+    pq.delete(7);
+    pq.validate().unwrap();
+    assert!(IvfConfig::new(dim).pq(5).build().is_err());
+
+    // saved with its kind like the others
+    let any: AnyIndex = pq.into();
+    let loaded = AnyIndex::from_bytes(&any.to_bytes().unwrap()).unwrap();
+    assert_eq!(loaded.kind(), IndexKind::Ivf);
+    assert_eq!(loaded, any);
+    assert_eq!(loaded.search_with_depth(&queries[0], 5, Some(2)), any.search_with_depth(&queries[0], 5, Some(2)));
+
+    let dir = std::env::temp_dir().join("photon_test_ivf");
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir(&dir).unwrap();
+    let mut db = PhotonDB::create_ivf(dir.join("db.pho"), IvfConfig::new(dim).nlist(4)).unwrap();
+    for v in &vectors[..100] {
+        db.add(v);
+    }
+    db.train().unwrap();
+    db.save().unwrap();
+    let loaded = PhotonDB::load(dir.join("db.pho"), dim).unwrap();
+    assert_eq!(loaded.kind(), IndexKind::Ivf);
+    assert_eq!(loaded.search(&vectors[3], 1)[0].1, 3);
+    let mut flat = PhotonDB::create_flat(dir.join("db.pho"), dim, Metric::Euclidean).unwrap();
+    assert!(flat.train().is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_vamana_disk_index() {
+    use photon_db::disk::SECTOR;
+    use photon_db::{DiskIndex, ReadMode, VamanaConfig};
+
+    let dim = 16;
+    let mut store = VectorStore::new(1000, dim);
+    for _ in 0..1000 {
+        store.insert(&generate_random_vector(dim));
+    }
+    let queries: Vec<Vec<f32>> = (0..20).map(|_| generate_random_vector(dim)).collect();
+    assert!(VamanaConfig::new().r(32).l_build(16).build(VectorStore::new(0, dim)).is_err());
+    assert!(VamanaConfig::new().alpha(0.5).build(VectorStore::new(0, dim)).is_err());
+
+    let graph = VamanaConfig::new().r(24).l_build(48).l_search(48).seed(5).build(store).unwrap();
+    assert!(graph.neighbors.iter().all(|n| !n.is_empty() && n.len() <= 24));
+    // share of the exact top 10 found, over all queries
+    let recall = |results: Vec<Vec<(f32, usize)>>| {
+        let mut hits = 0;
+        for (q, found) in queries.iter().zip(&results) {
+            let truth = graph.brute_force_search(q, 10);
+            hits += found.iter().filter(|(_, id)| truth.iter().any(|(_, t)| t == id)).count();
+        }
+        hits as f32 / (queries.len() * 10) as f32
+    };
+    let ram_recall = recall(queries.iter().map(|q| graph.search(q, 10, None)).collect());
+    assert!(ram_recall > 0.9, "in-RAM recall {}", ram_recall);
+
+    let dir = std::env::temp_dir().join("photon_test_vamana");
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir(&dir).unwrap();
+    let path = dir.join("graph.disk");
+    graph.save_disk(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len() as usize % SECTOR, 0);
+
+    // cold cache: nothing of the file is in memory when the searches start
+    if cfg!(target_os = "linux") {
+        photon_db::disk::drop_page_cache(&path).unwrap();
+    }
+    let mapped = DiskIndex::open(&path, ReadMode::Mmap).unwrap();
+    let pread = DiskIndex::open(&path, ReadMode::Pread).unwrap();
+    assert_eq!(mapped.len(), 1000);
+    for node in [0, 700, 999] {
+        let (vector, neighbors) = pread.read_node(node).unwrap();
+        assert_eq!(vector, graph.vectors.get(node));
+        assert_eq!(neighbors, graph.neighbors[node]);
+    }
+    assert!(pread.read_node(1000).is_err());
+
+    let disk_recall = recall(queries.iter().map(|q| mapped.search(q, 10, None).unwrap()).collect());
+    assert!(disk_recall > 0.85, "disk recall {}", disk_recall);
+    for q in &queries {
+        if cfg!(target_os = "linux") {
+            mapped.drop_page_cache().unwrap();
+        }
+        let (hits, stats) = mapped.search_with_stats(q, 10, None).unwrap();
+        assert_eq!(hits, pread.search(q, 10, None).unwrap());
+        // a few dozen block reads, not a scan
+        assert!(stats.hops > 0 && stats.hops < 100, "{} blocks read", stats.hops);
+    }
+    assert!(mapped.search(&[0.0; 3], 1, None).is_err());
+    fs::write(dir.join("bad.disk"), vec![0u8; SECTOR]).unwrap();
+    assert!(DiskIndex::open(&dir.join("bad.disk"), ReadMode::Pread).is_err());
+
+    // truncated file / neighbor id past n: errors, not panics
+    let mut bytes = fs::read(&path).unwrap();
+    fs::write(dir.join("bad.disk"), &bytes[..bytes.len() / 2]).unwrap();
+    assert!(DiskIndex::open(&dir.join("bad.disk"), ReadMode::Mmap).is_err());
+    let first_neighbor = SECTOR + dim * 4 + 4;
+    bytes[first_neighbor..first_neighbor + 4].copy_from_slice(&5000u32.to_le_bytes());
+    fs::write(dir.join("bad.disk"), &bytes).unwrap();
+    let bad = DiskIndex::open(&dir.join("bad.disk"), ReadMode::Mmap).unwrap();
+    assert!(bad.read_node(0).is_err());
+    assert!(bad.read_node(1).is_ok());
+
+    // huge dim / r in the header: an error instead of an overflow
+    for field in [2, 3] {
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[field * 8..field * 8 + 8].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+        fs::write(dir.join("bad.disk"), &bytes).unwrap();
+        assert!(DiskIndex::open(&dir.join("bad.disk"), ReadMode::Pread).is_err());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_tiered_vector_storage() {
+    use photon_db::{AnyIndex, DiskIndex, ReadMode, TierConfig, VamanaConfig};
+
+    let dim = 8;
+    let dir = std::env::temp_dir().join("photon_test_tiered");
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir(&dir).unwrap();
+    let file = dir.join("vectors.f32");
+
+    let mut hnsw = HnswConfig::new(dim).seed(11).build().unwrap();
+    let vectors: Vec<Vec<f32>> = (0..400).map(|_| generate_random_vector(dim)).collect();
+    for v in &vectors[..300] {
+        hnsw.insert(v);
+    }
+    let queries: Vec<Vec<f32>> = (0..10).map(|_| generate_random_vector(dim)).collect();
+    let before: Vec<_> = queries.iter().map(|q| hnsw.search(q, 5, None)).collect();
+
+    // room for 16 rows in RAM, the rest goes to the file
+    let budget = 16 * dim * 4;
+    hnsw.set_tier(TierConfig::new(file.to_str().unwrap()).memory_budget(budget)).unwrap();
+    assert!(hnsw.vectors.is_tiered());
+    assert_eq!(hnsw.vectors.ram_bytes(), 0);
+    let header = 32;
+    assert_eq!(fs::metadata(&file).unwrap().len() as usize, header + 300 * dim * 4);
+    assert!(hnsw.set_tier(TierConfig::new(file.to_str().unwrap())).is_err());
+    let after: Vec<_> = queries.iter().map(|q| hnsw.search(q, 5, None)).collect();
+    assert_eq!(before, after);
+
+    for v in &vectors[300..] {
+        hnsw.insert(v);
+        hnsw.enforce_memory_budget().unwrap();
+        assert!(hnsw.vectors.ram_bytes() <= budget);
+    }
+    assert!(hnsw.vectors.mapped_rows > 300);
+    assert_eq!(hnsw.vectors.len(), 400);
+    for id in [0, 299, 350, 399] {
+        assert_eq!(hnsw.get_vector(id), Some(vectors[id].as_slice()));
+    }
+
+    // the .pho keeps the graph + the RAM tail, loading maps the file again
+    let saved: Vec<_> = queries.iter().map(|q| hnsw.search(q, 5, None)).collect();
+    let any: AnyIndex = hnsw.into();
+    let bytes = any.to_bytes().unwrap();
+    let mut hnsw = match AnyIndex::from_bytes(&bytes).unwrap() {
+        AnyIndex::Hnsw(hnsw) => hnsw,
+        _ => unreachable!(),
+    };
+    let reloaded: Vec<_> = queries.iter().map(|q| hnsw.search(q, 5, None)).collect();
+    assert_eq!(reloaded, saved);
+    assert_eq!(hnsw.get_vector(399), Some(vectors[399].as_slice()));
+
+    // compact rewrites the file without the deleted rows
+    for id in 0..100 {
+        hnsw.delete(id);
+    }
+    assert_eq!(hnsw.compact(), Ok(100));
+    assert_eq!(fs::metadata(&file).unwrap().len() as usize, header + 300 * dim * 4);
+    // the .pho saved before compact would read the wrong rows now
+    assert!(AnyIndex::from_bytes(&bytes).unwrap_err().contains("rewritten"));
+    hnsw.validate().unwrap();
+    assert_eq!(hnsw.get_vector(150), Some(vectors[150].as_slice()));
+    assert_eq!(hnsw.get_vector(50), None);
+
+    hnsw.rebuild(HnswConfig::new(dim).seed(2)).unwrap();
+    assert!(hnsw.vectors.is_tiered());
+    assert_eq!(hnsw.get_vector(250), Some(vectors[250].as_slice()));
+
+    hnsw.vectors.load_into_memory();
+    assert!(!hnsw.vectors.is_tiered());
+    assert_eq!(hnsw.vectors.ram_bytes(), 300 * dim * 4);
+    assert_eq!(hnsw.get_vector(399), Some(vectors[399].as_slice()));
+
+    // missing file -> load error instead of a crash later
+    fs::remove_file(&file).unwrap();
+    assert!(AnyIndex::from_bytes(&bytes).is_err());
+
+    // a tiered store (mostly mapped rows) works as Vamana / disk index input
+    let mut store = VectorStore::new(0, dim);
+    for v in &vectors {
+        store.insert(v);
+    }
+    store.set_tier(TierConfig::new(file.to_str().unwrap())).unwrap();
+    store.insert(&vectors[0]);
+    let graph = VamanaConfig::new().r(16).l_build(32).seed(3).build(store).unwrap();
+    let disk = dir.join("graph.disk");
+    graph.save_disk(&disk).unwrap();
+    let opened = DiskIndex::open(&disk, ReadMode::Pread).unwrap();
+    assert_eq!(opened.search(&vectors[7], 1, None).unwrap()[0], (0.0, 7));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// base layer edges between slots at most 8 apart
+fn short_edges(hnsw: &HNSW) -> usize {
+    let base = &hnsw.layers.base_layer;
+    base.iter().enumerate().map(|(node, list)| list.iter().filter(|&&n| node.abs_diff(n) <= 8).count()).sum()
+}
+
+#[test]
+fn test_reorder_keeps_ids_and_results() {
+    use photon_db::ReorderStrategy;
+
+    let dim = 8;
+    let vectors: Vec<Vec<f32>> = (0..500).map(|_| generate_random_vector(dim)).collect();
+    let queries: Vec<Vec<f32>> = (0..10).map(|_| generate_random_vector(dim)).collect();
+    assert!(ReorderStrategy::parse("random").is_err());
+
+    for strategy in ["bfs", "rcm", "gorder"] {
+        let mut hnsw = HnswConfig::new(dim).seed(4).build().unwrap();
+        for (i, v) in vectors.iter().enumerate() {
+            hnsw.insert_with_payload(v, text_payload(&format!("doc {}", i)));
+        }
+        hnsw.delete(7);
+        let before: Vec<_> = queries.iter().map(|q| hnsw.search(q, 5, None)).collect();
+        let gap = hnsw.average_edge_gap();
+        let short = short_edges(&hnsw);
+
+        let remap = hnsw.reorder(ReorderStrategy::parse(strategy).unwrap()).unwrap();
+        assert_eq!(remap.len(), 500);
+        let mut sorted = remap.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..500).collect::<Vec<_>>(), "{} is not a permutation", strategy);
+        hnsw.validate().unwrap();
+        if strategy == "gorder" {
+            // gorder optimizes for neighbors within a few slots, not the mean gap
+            assert!(short_edges(&hnsw) > 2 * short, "gorder: short edges {} -> {}", short, short_edges(&hnsw));
+        } else {
+            assert!(hnsw.average_edge_gap() < gap, "{}: gap {} -> {}", strategy, gap, hnsw.average_edge_gap());
+        }
+
+        // same graph, renamed: identical results, ids and payloads untouched
+        let after: Vec<_> = queries.iter().map(|q| hnsw.search(q, 5, None)).collect();
+        assert_eq!(before, after, "{}", strategy);
+        assert_eq!(hnsw.get_vector(123), Some(vectors[123].as_slice()));
+        assert_eq!(hnsw.get_vector(7), None);
+        assert_eq!(hnsw.slot_of(123), Some(remap[123]));
+        assert_eq!(hnsw.payload(499), Some(&text_payload("doc 499")));
+        let id = hnsw.insert(&vectors[0]);
+        assert_eq!(id, 500);
+        assert_eq!(hnsw.search(&vectors[0], 2, None).len(), 2);
+    }
+
+    // a tiered store whose file can't be rewritten: an error, and the index untouched
+    let dir = std::env::temp_dir().join("photon_test_reorder");
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir(&dir).unwrap();
+    let mut hnsw = HnswConfig::new(dim).seed(4).build().unwrap();
+    for v in &vectors {
+        hnsw.insert(v);
+    }
+    hnsw.set_tier(photon_db::TierConfig::new(dir.join("vectors.f32").to_str().unwrap())).unwrap();
+    let before: Vec<_> = queries.iter().map(|q| hnsw.search(q, 5, None)).collect();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(hnsw.reorder(ReorderStrategy::Gorder).is_err());
+    hnsw.validate().unwrap();
+    let after: Vec<_> = queries.iter().map(|q| hnsw.search(q, 5, None)).collect();
+    assert_eq!(before, after);
+}